@@ -0,0 +1,4 @@
+pub mod hebrew_date;
+pub mod learning;
+pub mod parasha;
+pub mod plan;
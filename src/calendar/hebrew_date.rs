@@ -0,0 +1,436 @@
+// The fixed Hebrew calendar: every year starts on the day of the molad of Tishri, give or take
+// the dechiyot, and everything else follows from how long the year then turns out to be.
+use anyhow::anyhow;
+use chrono::{Datelike, Local, NaiveDate};
+use std::fmt;
+use std::str::FromStr;
+
+// 1 Tishri of year 1 counted from 1 January 1 CE, less the day it is in `elapsed_days`
+static EPOCH: i64 = -1373428;
+// A day is 24 hours of 1080 parts
+static PARTS_PER_HOUR: i64 = 1080;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Month {
+    Tishri,
+    Cheshvan,
+    Kislev,
+    Tevet,
+    Shevat,
+    // Only in usual years, leap years have two
+    Adar,
+    AdarI,
+    AdarII,
+    Nisan,
+    Iyar,
+    Sivan,
+    Tammuz,
+    Av,
+    Elul,
+}
+
+impl Month {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Month::Tishri => "Tishri",
+            Month::Cheshvan => "Cheshvan",
+            Month::Kislev => "Kislev",
+            Month::Tevet => "Tevet",
+            Month::Shevat => "Shevat",
+            Month::Adar => "Adar",
+            Month::AdarI => "Adar I",
+            Month::AdarII => "Adar II",
+            Month::Nisan => "Nisan",
+            Month::Iyar => "Iyar",
+            Month::Sivan => "Sivan",
+            Month::Tammuz => "Tammuz",
+            Month::Av => "Av",
+            Month::Elul => "Elul",
+        }
+    }
+
+    pub fn hebrew_name(&self) -> &'static str {
+        match self {
+            Month::Tishri => "תשרי",
+            Month::Cheshvan => "חשון",
+            Month::Kislev => "כסלו",
+            Month::Tevet => "טבת",
+            Month::Shevat => "שבט",
+            Month::Adar => "אדר",
+            Month::AdarI => "אדר א׳",
+            Month::AdarII => "אדר ב׳",
+            Month::Nisan => "ניסן",
+            Month::Iyar => "אייר",
+            Month::Sivan => "סיון",
+            Month::Tammuz => "תמוז",
+            Month::Av => "אב",
+            Month::Elul => "אלול",
+        }
+    }
+}
+
+impl FromStr for Month {
+    type Err = anyhow::Error;
+
+    /// Most spellings of a month, in English or Hebrew.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let simplified: String = s
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect();
+        Ok(match simplified.as_str() {
+            "tishri" | "tishrei" | "תשרי" => Month::Tishri,
+            "cheshvan" | "heshvan" | "marcheshvan" | "marheshvan" | "חשון" | "חשוון" | "מרחשון"
+            | "מרחשוון" => Month::Cheshvan,
+            "kislev" | "kislew" | "כסלו" => Month::Kislev,
+            "tevet" | "teves" | "tebeth" | "טבת" => Month::Tevet,
+            "shevat" | "shvat" | "shevet" | "שבט" => Month::Shevat,
+            "adar" | "אדר" => Month::Adar,
+            "adari" | "adar1" | "adaraleph" | "adara" | "אדרא" => Month::AdarI,
+            "adarii" | "adar2" | "adarbet" | "adarbeis" | "adarb" | "אדרב" => Month::AdarII,
+            "nisan" | "nissan" | "ניסן" => Month::Nisan,
+            "iyar" | "iyyar" | "אייר" | "איר" => Month::Iyar,
+            "sivan" | "סיון" | "סיוון" => Month::Sivan,
+            "tammuz" | "tamuz" | "תמוז" => Month::Tammuz,
+            "av" | "menachemav" | "אב" | "מנחםאב" => Month::Av,
+            "elul" | "אלול" => Month::Elul,
+            _ => return Err(anyhow!("There is no Hebrew month called '{}'", s)),
+        })
+    }
+}
+
+pub fn is_leap_year(year: i64) -> bool {
+    (7 * year + 1).rem_euclid(19) < 7
+}
+
+/// The months of a year in order, starting from Tishri.
+pub fn months(year: i64) -> Vec<Month> {
+    let adar: &[Month] = if is_leap_year(year) {
+        &[Month::AdarI, Month::AdarII]
+    } else {
+        &[Month::Adar]
+    };
+    [
+        &[
+            Month::Tishri,
+            Month::Cheshvan,
+            Month::Kislev,
+            Month::Tevet,
+            Month::Shevat,
+        ][..],
+        adar,
+        &[
+            Month::Nisan,
+            Month::Iyar,
+            Month::Sivan,
+            Month::Tammuz,
+            Month::Av,
+            Month::Elul,
+        ],
+    ]
+    .concat()
+}
+
+/// Days from the epoch to 1 Tishri of `year`, which is the day of the molad of Tishri unless
+/// one of the dechiyot postpones it.
+fn elapsed_days(year: i64) -> i64 {
+    // 19 years to a cycle, 7 of them with an extra month
+    let months = (235 * year - 234).div_euclid(19);
+    // The molad of Tishri of year 1 was 5 hours and 204 parts into the day, and every month
+    // after it is 29 days, 12 hours and 793 parts later
+    let parts_elapsed = 204 + 793 * (months % PARTS_PER_HOUR);
+    let hours_elapsed =
+        5 + 12 * months + 793 * (months / PARTS_PER_HOUR) + parts_elapsed / PARTS_PER_HOUR;
+    let day = 1 + 29 * months + hours_elapsed / 24;
+    let parts = PARTS_PER_HOUR * (hours_elapsed % 24) + parts_elapsed % PARTS_PER_HOUR;
+
+    let mut new_year = day;
+    // Molad zaken: a molad after noon pushes the new year off a day, as do GaTaRaD and BeTUTaKPaT,
+    // which keep years from being too long or too short
+    if parts >= 18 * PARTS_PER_HOUR
+        || (day % 7 == 2 && parts >= 9 * PARTS_PER_HOUR + 204 && !is_leap_year(year))
+        || (day % 7 == 1 && parts >= 15 * PARTS_PER_HOUR + 589 && is_leap_year(year - 1))
+    {
+        new_year += 1;
+    }
+    // Lo ADU Rosh: never on a Sunday, Wednesday or Friday
+    if matches!(new_year % 7, 0 | 3 | 5) {
+        new_year += 1;
+    }
+    new_year
+}
+
+/// 1 Tishri of `year` counted like `num_days_from_ce`, where 1 January 1 CE is 1.
+fn new_year(year: i64) -> i64 {
+    EPOCH + elapsed_days(year)
+}
+
+pub fn days_in_year(year: i64) -> i64 {
+    new_year(year + 1) - new_year(year)
+}
+
+pub fn days_in_month(year: i64, month: Month) -> i64 {
+    match month {
+        // Full years have a long Cheshvan and short years a short Kislev
+        Month::Cheshvan if days_in_year(year) % 10 == 5 => 30,
+        Month::Kislev if days_in_year(year) % 10 == 3 => 29,
+        Month::Tishri | Month::Kislev | Month::Shevat | Month::AdarI | Month::Nisan => 30,
+        Month::Sivan | Month::Av => 30,
+        _ => 29,
+    }
+}
+
+/// A number in Hebrew letters, like `תשפ״ה` for 785, with ט״ו and ט״ז for 15 and 16 so as not to
+/// spell a name of God.
+pub fn gematria(number: u32) -> String {
+    static HUNDREDS: [char; 4] = ['ק', 'ר', 'ש', 'ת'];
+    static TENS: [char; 9] = ['י', 'כ', 'ל', 'מ', 'נ', 'ס', 'ע', 'פ', 'צ'];
+    static ONES: [char; 9] = ['א', 'ב', 'ג', 'ד', 'ה', 'ו', 'ז', 'ח', 'ט'];
+
+    let mut letters: Vec<char> = vec![];
+    let mut number = number;
+    while number >= 400 {
+        letters.push('ת');
+        number -= 400;
+    }
+    if number >= 100 {
+        letters.push(HUNDREDS[number as usize / 100 - 1]);
+        number %= 100;
+    }
+    if number == 15 || number == 16 {
+        letters.extend(['ט', ONES[number as usize - 10]]);
+        number = 0;
+    }
+    if number >= 10 {
+        letters.push(TENS[number as usize / 10 - 1]);
+        number %= 10;
+    }
+    if number > 0 {
+        letters.push(ONES[number as usize - 1]);
+    }
+
+    match letters.len() {
+        0 => String::new(),
+        1 => format!("{}׳", letters[0]),
+        len => {
+            letters.insert(len - 1, '״');
+            letters.into_iter().collect()
+        }
+    }
+}
+
+/// The inverse of [`gematria`], allowing final letters and leaving out punctuation.
+fn parse_gematria(text: &str) -> Option<u32> {
+    let mut total = 0;
+    for c in text.chars() {
+        total += match c {
+            'א'..='ט' => c as u32 - 'א' as u32 + 1,
+            'י' => 10,
+            'כ' | 'ך' => 20,
+            'ל' => 30,
+            'מ' | 'ם' => 40,
+            'נ' | 'ן' => 50,
+            'ס' => 60,
+            'ע' => 70,
+            'פ' | 'ף' => 80,
+            'צ' | 'ץ' => 90,
+            'ק' => 100,
+            'ר' => 200,
+            'ש' => 300,
+            'ת' => 400,
+            '׳' | '״' | '\'' | '"' => 0,
+            _ => return None,
+        };
+    }
+    (total > 0).then_some(total)
+}
+
+/// A day in the Hebrew calendar. It really starts the evening before the day it is converted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HebrewDate {
+    pub year: i64,
+    pub month: Month,
+    pub day: i64,
+}
+
+impl HebrewDate {
+    pub fn from_gregorian(date: NaiveDate) -> HebrewDate {
+        let days = date.num_days_from_ce() as i64;
+        // Years are never longer than 385 days, so this is at most a few years early
+        let mut year = (days - EPOCH) / 366;
+        while new_year(year + 1) <= days {
+            year += 1;
+        }
+
+        let mut day = days - new_year(year) + 1;
+        for month in months(year) {
+            let length = days_in_month(year, month);
+            if day <= length {
+                return HebrewDate { year, month, day };
+            }
+            day -= length;
+        }
+        unreachable!("days_in_year is the sum of days_in_month")
+    }
+
+    pub fn to_gregorian(self) -> Result<NaiveDate, anyhow::Error> {
+        // Adar is Adar II in a leap year, as it is for Purim, and either Adar is Adar otherwise
+        let month = match (self.month, is_leap_year(self.year)) {
+            (Month::Adar, true) => Month::AdarII,
+            (Month::AdarI | Month::AdarII, false) => Month::Adar,
+            (month, _) => month,
+        };
+        let length = days_in_month(self.year, month);
+        if self.year < 1 || self.day < 1 || self.day > length {
+            return Err(anyhow!(
+                "{} {} only has {} days",
+                month.name(),
+                self.year,
+                length
+            ));
+        }
+
+        let before: i64 = months(self.year)
+            .into_iter()
+            .take_while(|found| *found != month)
+            .map(|found| days_in_month(self.year, found))
+            .sum();
+        let days = new_year(self.year) + before + self.day - 1;
+        i32::try_from(days)
+            .ok()
+            .and_then(NaiveDate::from_num_days_from_ce_opt)
+            .ok_or(anyhow!("{} is too far from today", self))
+    }
+
+    /// The date in Hebrew letters, like `כ״ה כסלו תשפ״ה`.
+    pub fn hebrew(&self) -> String {
+        format!(
+            "{} {} {}",
+            gematria(self.day as u32),
+            self.month.hebrew_name(),
+            gematria(u32::try_from(self.year.rem_euclid(1000)).unwrap_or_default())
+        )
+    }
+}
+
+impl fmt::Display for HebrewDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.day, self.month.name(), self.year)
+    }
+}
+
+impl FromStr for HebrewDate {
+    type Err = anyhow::Error;
+
+    /// `25 Kislev 5785` or `כ״ה כסלו תשפ״ה`, where a year without thousands is in this millennium.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let [day, month @ .., year] = words.as_slice() else {
+            return Err(anyhow!("Could not parse Hebrew date: {}", s));
+        };
+        if month.is_empty() {
+            return Err(anyhow!("Could not parse Hebrew date: {}", s));
+        }
+        let number = |word: &str| {
+            word.parse::<u32>()
+                .ok()
+                .or_else(|| parse_gematria(word))
+                .ok_or(anyhow!("Could not parse '{}' as a number", word))
+        };
+        let year = match number(year)? {
+            year if year < 1000 => year + 5000,
+            year => year,
+        };
+        Ok(HebrewDate {
+            year: year as i64,
+            month: month.join(" ").parse()?,
+            day: number(day)? as i64,
+        })
+    }
+}
+
+/// `YYYY-MM-DD` or a Hebrew date, or today if there is nothing to parse. Years before 1 are
+/// turned away, since every Hebrew year before the first would be too.
+pub fn parse_date(text: &str) -> Result<NaiveDate, anyhow::Error> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(Local::now().date_naive());
+    }
+    match NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        Ok(date) if date.year() < 1 => Err(anyhow!(
+            "Dates before the year 1 are not supported: {}",
+            text
+        )),
+        Ok(date) => Ok(date),
+        Err(_) => text.parse::<HebrewDate>()?.to_gregorian(),
+    }
+}
+
+/// A day in both calendars, in Hebrew and transliterated.
+pub fn format_date(date: NaiveDate) -> String {
+    let hebrew_date = HebrewDate::from_gregorian(date);
+    let evening = HebrewDate::from_gregorian(date.succ_opt().unwrap_or(date));
+    format!(
+        "# {}\n{}\n\n{}\n*From sunset: {}*",
+        hebrew_date,
+        hebrew_date.hebrew(),
+        date.format("%A, %-d %B %Y"),
+        evening
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn converts_dates() {
+        let known = [
+            ("2024-10-03", "1 Tishri 5785"),
+            ("2023-09-16", "1 Tishri 5784"),
+            ("2025-09-23", "1 Tishri 5786"),
+            ("2025-04-13", "15 Nisan 5785"),
+            ("2024-03-24", "14 Adar II 5784"),
+            ("2024-12-26", "25 Kislev 5785"),
+            ("1948-05-14", "5 Iyar 5708"),
+            ("2000-01-01", "23 Tevet 5760"),
+        ];
+        for (gregorian, hebrew) in known {
+            let converted = HebrewDate::from_gregorian(date(gregorian));
+            assert_eq!(converted.to_string(), hebrew);
+            assert_eq!(converted.to_gregorian().unwrap(), date(gregorian));
+        }
+        for day in date("2020-01-01").iter_days().take(1000) {
+            assert_eq!(HebrewDate::from_gregorian(day).to_gregorian().unwrap(), day);
+        }
+    }
+
+    #[test]
+    fn year_lengths() {
+        for year in 5700..5800 {
+            assert!(matches!(days_in_year(year), 353..=355 | 383..=385));
+        }
+        assert_eq!(days_in_year(5784), 383);
+    }
+
+    #[test]
+    fn hebrew_dates() {
+        let date: HebrewDate = "כ״ה כסלו תשפ״ה".parse().unwrap();
+        assert_eq!(date, "25 Kislev 5785".parse().unwrap());
+        assert_eq!(date.hebrew(), "כ״ה כסלו תשפ״ה");
+        assert_eq!(gematria(15), "ט״ו");
+        assert_eq!(gematria(1), "א׳");
+        assert!("30 Cheshvan 5784"
+            .parse::<HebrewDate>()
+            .unwrap()
+            .to_gregorian()
+            .is_err());
+        assert!(parse_date("-4000-01-01").is_err());
+        assert!(parse_date("0001-01-01").is_ok());
+    }
+}
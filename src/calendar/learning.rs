@@ -0,0 +1,766 @@
+// Daily learning cycles, each worked out from the day it started and how long what it learns is.
+use anyhow::anyhow;
+use chrono::{Datelike, NaiveDate, Weekday};
+use clap::ValueEnum;
+
+use super::hebrew_date::{HebrewDate, Month};
+use crate::parser::shape::{category_shapes, ShapeContents};
+use crate::parser::toc::{books, find_category, Toc};
+
+/// A daily learning cycle, in the order `today` lists them.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Cycle {
+    /// A daf of the Babylonian Talmud
+    Daf,
+    // Only listed, since Sefaria divides the Yerushalmi by halakha rather than by Vilna daf
+    #[value(skip)]
+    Yerushalmi,
+    /// Two mishnayot
+    Mishnah,
+    /// A chapter of the Mishneh Torah
+    Rambam,
+    /// Three chapters of the Mishneh Torah
+    Rambam3,
+    /// A chapter of Tanakh, Sunday to Thursday
+    #[value(name = "929")]
+    Tanakh,
+    /// Psalms by the day of the month
+    Tehillim,
+}
+
+impl Cycle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cycle::Daf => "Daf Yomi",
+            Cycle::Yerushalmi => "Yerushalmi Yomi",
+            Cycle::Mishnah => "Mishnah Yomit",
+            Cycle::Rambam => "Rambam (1 chapter)",
+            Cycle::Rambam3 => "Rambam (3 chapters)",
+            Cycle::Tanakh => "929",
+            Cycle::Tehillim => "Tehillim",
+        }
+    }
+}
+
+/// What a cycle learns on a day, as a name and the refs to show for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Learning {
+    pub cycle: Cycle,
+    pub name: String,
+    // Empty when Sefaria has nothing to show for it
+    pub refs: Vec<String>,
+}
+
+// Every tractate in the order of Daf Yomi with its first and last daf. Kinnim, Tamid and Middot
+// share the end of Meilah's pages, so they go on from where it stops.
+static BAVLI: [(&str, usize, usize); 40] = [
+    ("Berakhot", 2, 64),
+    ("Shabbat", 2, 157),
+    ("Eruvin", 2, 105),
+    ("Pesachim", 2, 121),
+    ("Shekalim", 2, 22),
+    ("Yoma", 2, 88),
+    ("Sukkah", 2, 56),
+    ("Beitzah", 2, 40),
+    ("Rosh Hashanah", 2, 35),
+    ("Taanit", 2, 31),
+    ("Megillah", 2, 32),
+    ("Moed Katan", 2, 29),
+    ("Chagigah", 2, 27),
+    ("Yevamot", 2, 122),
+    ("Ketubot", 2, 112),
+    ("Nedarim", 2, 91),
+    ("Nazir", 2, 66),
+    ("Sotah", 2, 49),
+    ("Gittin", 2, 90),
+    ("Kiddushin", 2, 82),
+    ("Bava Kamma", 2, 119),
+    ("Bava Metzia", 2, 119),
+    ("Bava Batra", 2, 176),
+    ("Sanhedrin", 2, 113),
+    ("Makkot", 2, 24),
+    ("Shevuot", 2, 49),
+    ("Avodah Zarah", 2, 76),
+    ("Horayot", 2, 14),
+    ("Zevachim", 2, 120),
+    ("Menachot", 2, 110),
+    ("Chullin", 2, 142),
+    ("Bekhorot", 2, 61),
+    ("Arakhin", 2, 34),
+    ("Temurah", 2, 34),
+    ("Keritot", 2, 28),
+    ("Meilah", 2, 22),
+    ("Kinnim", 23, 25),
+    ("Tamid", 26, 33),
+    ("Middot", 34, 37),
+    ("Niddah", 2, 73),
+];
+// The first cycle started on 11 September 1923, and until the eighth, Shekalim was learned with
+// only 12 dafim
+static DAF_YOMI_START: (i32, u32, u32) = (1923, 9, 11);
+static DAF_YOMI_EIGHTH_CYCLE: (i32, u32, u32) = (1975, 6, 24);
+static OLD_SHEKALIM: usize = 13;
+
+// Every tractate of the Vilna Yerushalmi with how many dafim it has
+static YERUSHALMI: [(&str, usize); 39] = [
+    ("Berakhot", 68),
+    ("Peah", 37),
+    ("Demai", 34),
+    ("Kilayim", 44),
+    ("Sheviit", 31),
+    ("Terumot", 59),
+    ("Maasrot", 26),
+    ("Maaser Sheni", 33),
+    ("Challah", 28),
+    ("Orlah", 20),
+    ("Bikkurim", 13),
+    ("Shabbat", 92),
+    ("Eruvin", 65),
+    ("Pesachim", 71),
+    ("Beitzah", 22),
+    ("Rosh Hashanah", 22),
+    ("Yoma", 42),
+    ("Sukkah", 26),
+    ("Taanit", 26),
+    ("Shekalim", 33),
+    ("Megillah", 34),
+    ("Chagigah", 22),
+    ("Moed Katan", 19),
+    ("Yevamot", 85),
+    ("Ketubot", 72),
+    ("Sotah", 47),
+    ("Nedarim", 40),
+    ("Nazir", 47),
+    ("Gittin", 54),
+    ("Kiddushin", 48),
+    ("Bava Kamma", 44),
+    ("Bava Metzia", 37),
+    ("Bava Batra", 34),
+    ("Shevuot", 44),
+    ("Makkot", 9),
+    ("Sanhedrin", 57),
+    ("Avodah Zarah", 37),
+    ("Horayot", 19),
+    ("Niddah", 13),
+];
+static YERUSHALMI_START: (i32, u32, u32) = (1980, 2, 2);
+
+// Every part of the Mishneh Torah in the order of the Rambam calendar with how many chapters it
+// has. The introduction is learned as 17 chapters, which with the 1000 chapters of the halakhot
+// makes the 339 days of three chapters a day and the 1017 days of one.
+static RAMBAM: [(&str, usize); 87] = [
+    ("Transmission of the Oral Law", 1),
+    ("Positive Mitzvot", 1),
+    ("Negative Mitzvot", 1),
+    ("Overview of Mishneh Torah Contents", 14),
+    ("Foundations of the Torah", 10),
+    ("Human Dispositions", 7),
+    ("Torah Study", 7),
+    ("Foreign Worship and Customs of the Nations", 12),
+    ("Repentance", 10),
+    ("Reading the Shema", 4),
+    ("Prayer and the Priestly Blessing", 15),
+    ("Tefillin, Mezuzah and the Torah Scroll", 10),
+    ("Fringes", 3),
+    ("Blessings", 11),
+    ("Circumcision", 3),
+    ("Sabbath", 30),
+    ("Eruvin", 8),
+    ("Rest on the Tenth of Tishrei", 3),
+    ("Rest on a Holiday", 8),
+    ("Leavened and Unleavened Bread", 8),
+    ("Shofar, Sukkah and Lulav", 8),
+    ("Sheqel Dues", 4),
+    ("Sanctification of the New Month", 19),
+    ("Fasts", 5),
+    ("Scroll of Esther and Hanukkah", 4),
+    ("Marriage", 25),
+    ("Divorce", 13),
+    ("Levirate Marriage and Release", 8),
+    ("Virgin Maiden", 3),
+    ("Woman Suspected of Infidelity", 4),
+    ("Forbidden Intercourse", 22),
+    ("Forbidden Foods", 17),
+    ("Ritual Slaughter", 14),
+    ("Oaths", 12),
+    ("Vows", 13),
+    ("Nazariteship", 10),
+    ("Appraisals and Devoted Property", 8),
+    ("Diverse Species", 10),
+    ("Gifts to the Poor", 10),
+    ("Heave Offerings", 15),
+    ("Tithes", 14),
+    ("Second Tithes and Fourth Year's Fruit", 11),
+    (
+        "First Fruits and other Gifts to Priests Outside the Sanctuary",
+        12,
+    ),
+    ("Sabbatical Year and the Jubilee", 13),
+    ("The Chosen Temple", 8),
+    ("Vessels of the Sanctuary and Those who Serve Therein", 10),
+    ("Admission into the Sanctuary", 9),
+    ("Things Forbidden on the Altar", 7),
+    ("Sacrificial Procedure", 19),
+    ("Daily Offerings and Additional Offerings", 10),
+    ("Sacrifices Rendered Unfit", 19),
+    ("Service on the Day of Atonement", 5),
+    ("Trespass", 8),
+    ("Paschal Offering", 10),
+    ("Festival Offering", 3),
+    ("Firstlings", 8),
+    ("Offerings for Unintentional Transgressions", 15),
+    ("Offerings for Those with Incomplete Atonement", 5),
+    ("Substitution", 4),
+    ("Defilement by a Corpse", 25),
+    ("Red Heifer", 15),
+    ("Defilement by Leprosy", 16),
+    ("Those Who Defile Bed or Seat", 13),
+    ("Other Sources of Defilement", 20),
+    ("Defilement of Foods", 16),
+    ("Vessels", 28),
+    ("Immersion Pools", 11),
+    ("Damages to Property", 14),
+    ("Theft", 9),
+    ("Robbery and Lost Property", 18),
+    ("One Who Injures a Person or Property", 8),
+    ("Murderer and the Preservation of Life", 13),
+    ("Sales", 30),
+    ("Ownerless Property and Gifts", 12),
+    ("Neighbors", 14),
+    ("Agents and Partners", 10),
+    ("Slaves", 9),
+    ("Hiring", 13),
+    ("Borrowing and Deposit", 8),
+    ("Creditor and Debtor", 27),
+    ("Plaintiff and Defendant", 16),
+    ("Inheritances", 11),
+    (
+        "The Sanhedrin and the Penalties within their Jurisdiction",
+        26,
+    ),
+    ("Testimony", 22),
+    ("Rebels", 7),
+    ("Mourning", 14),
+    ("Kings and Wars", 12),
+];
+
+// Only the text itself is learned, not the commentaries filed alongside it
+static MISHNAH_SEDARIM: [&str; 6] = [
+    "Mishnah/Seder Zeraim",
+    "Mishnah/Seder Moed",
+    "Mishnah/Seder Nashim",
+    "Mishnah/Seder Nezikin",
+    "Mishnah/Seder Kodashim",
+    "Mishnah/Seder Tahorot",
+];
+static TANAKH_PARTS: [&str; 3] = ["Tanakh/Torah", "Tanakh/Prophets", "Tanakh/Writings"];
+
+static MISHNAH_YOMIT_START: (i32, u32, u32) = (1947, 5, 20);
+static RAMBAM_START: (i32, u32, u32) = (1984, 4, 29);
+static NINE_TWENTY_NINE_START: (i32, u32, u32) = (2014, 12, 21);
+
+// The psalms said on each day of the month, with the 29th finishing the book in a short month
+static TEHILLIM: [&str; 30] = [
+    "1-9",
+    "10-17",
+    "18-22",
+    "23-28",
+    "29-34",
+    "35-38",
+    "39-43",
+    "44-48",
+    "49-54",
+    "55-59",
+    "60-65",
+    "66-68",
+    "69-71",
+    "72-76",
+    "77-78",
+    "79-82",
+    "83-87",
+    "88-89",
+    "90-96",
+    "97-103",
+    "104-105",
+    "106-107",
+    "108-112",
+    "113-118",
+    "119:1-96",
+    "119:97-176",
+    "120-134",
+    "135-139",
+    "140-144",
+    "145-150",
+];
+
+fn start(date: (i32, u32, u32)) -> NaiveDate {
+    NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap()
+}
+
+fn days_since(date: NaiveDate, epoch: (i32, u32, u32), cycle: &str) -> Result<i64, anyhow::Error> {
+    let epoch = start(epoch);
+    if date < epoch {
+        return Err(anyhow!(
+            "{} started on {}",
+            cycle,
+            epoch.format("%-d %B %Y")
+        ));
+    }
+    Ok((date - epoch).num_days())
+}
+
+pub fn daf_yomi(date: NaiveDate) -> Result<Learning, anyhow::Error> {
+    let mut tractates = BAVLI;
+    let days = if date < start(DAF_YOMI_EIGHTH_CYCLE) {
+        tractates[4].2 = OLD_SHEKALIM;
+        days_since(date, DAF_YOMI_START, "Daf Yomi")?
+    } else {
+        days_since(date, DAF_YOMI_EIGHTH_CYCLE, "Daf Yomi")?
+    };
+    let length: usize = tractates
+        .iter()
+        .map(|(_, first, last)| last - first + 1)
+        .sum();
+
+    let mut day = days as usize % length;
+    for (tractate, first, last) in tractates {
+        if day <= last - first {
+            let daf = first + day;
+            return Ok(Learning {
+                cycle: Cycle::Daf,
+                name: format!("{} {}", tractate, daf),
+                // Sefaria only has the Mishnah of Middot
+                refs: if tractate == "Middot" {
+                    vec![]
+                } else {
+                    vec![format!("{} {}a-{}b", tractate, daf, daf)]
+                },
+            });
+        }
+        day -= last - first + 1;
+    }
+    unreachable!("the day is taken modulo the length of the cycle")
+}
+
+/// No daf is learned on Yom Kippur or Tisha B'Av, which is put off to Sunday when it's on Shabbat.
+fn is_fast(date: NaiveDate) -> bool {
+    let hebrew_date = HebrewDate::from_gregorian(date);
+    match (hebrew_date.month, hebrew_date.day) {
+        (Month::Tishri, 10) => true,
+        (Month::Av, 9) => date.weekday() != Weekday::Sat,
+        (Month::Av, 10) => date.weekday() == Weekday::Sun,
+        _ => false,
+    }
+}
+
+pub fn yerushalmi_yomi(date: NaiveDate) -> Result<Learning, anyhow::Error> {
+    days_since(date, YERUSHALMI_START, "Yerushalmi Yomi")?;
+    if is_fast(date) {
+        return Ok(Learning {
+            cycle: Cycle::Yerushalmi,
+            name: "No daf today".to_string(),
+            refs: vec![],
+        });
+    }
+    let length: usize = YERUSHALMI.iter().map(|(_, dafim)| dafim).sum();
+    let days = start(YERUSHALMI_START)
+        .iter_days()
+        .take_while(|day| *day < date)
+        .filter(|day| !is_fast(*day))
+        .count();
+
+    let mut day = days % length;
+    for (tractate, dafim) in YERUSHALMI {
+        if day < dafim {
+            return Ok(Learning {
+                cycle: Cycle::Yerushalmi,
+                name: format!("{} {} (Vilna daf, not on Sefaria)", tractate, day + 1),
+                refs: vec![],
+            });
+        }
+        day -= dafim;
+    }
+    unreachable!("the day is taken modulo the length of the cycle")
+}
+
+/// The books of every one of `parts` in turn, each being a category like `Tanakh/Torah`.
+fn parts_books(
+    xdg_dirs: &xdg::BaseDirectories,
+    toc: &Toc,
+    parts: &[&str],
+) -> Result<Vec<ShapeContents>, anyhow::Error> {
+    let mut books = vec![];
+    for part in parts {
+        books.extend(cycle_books(xdg_dirs, toc, part)?);
+    }
+    Ok(books)
+}
+
+/// The books of a category in the order of the table of contents, with their shapes.
+pub fn cycle_books(
+    xdg_dirs: &xdg::BaseDirectories,
+    toc: &Toc,
+    category: &str,
+) -> Result<Vec<ShapeContents>, anyhow::Error> {
+    let (path, found) = find_category(toc, category)
+        .ok_or(anyhow!("There is no {} in the table of contents", category))?;
+    let shapes = category_shapes(xdg_dirs, &path)?;
+    Ok(books(&found.contents)
+        .iter()
+        .filter_map(|book| shapes.iter().find(|shape| shape.title == book.title))
+        .cloned()
+        .collect())
+}
+
+/// Groups `(book, chapter, segment)` positions into one ref per book.
+pub fn span_refs(
+    books: &[ShapeContents],
+    positions: &[(usize, usize, Option<usize>)],
+) -> Vec<String> {
+    let mut refs = vec![];
+    let mut rest = positions;
+    while let Some((book, first_chapter, first_segment)) = rest.first().copied() {
+        let count = rest
+            .iter()
+            .take_while(|position| position.0 == book)
+            .count();
+        let (_, last_chapter, last_segment) = rest[count - 1];
+        let title = &books[book].title;
+        refs.push(match (first_segment, last_segment) {
+            (Some(first), Some(last)) if first_chapter == last_chapter && first == last => {
+                format!("{} {}:{}", title, first_chapter, first)
+            }
+            (Some(first), Some(last)) if first_chapter == last_chapter => {
+                format!("{} {}:{}-{}", title, first_chapter, first, last)
+            }
+            (Some(first), Some(last)) => format!(
+                "{} {}:{}-{}:{}",
+                title, first_chapter, first, last_chapter, last
+            ),
+            // A book of one chapter is learned whole
+            _ if books[book].chapters.len() == 1 => title.clone(),
+            _ if first_chapter == last_chapter => {
+                format!("{} {}", title, books[book].section(first_chapter))
+            }
+            _ => format!(
+                "{} {}-{}",
+                title,
+                books[book].section(first_chapter),
+                books[book].section(last_chapter)
+            ),
+        });
+        rest = &rest[count..];
+    }
+    refs
+}
+
+/// Every chapter of `books` in order, or every segment of them with `segments`.
+pub fn positions(books: &[ShapeContents], segments: bool) -> Vec<(usize, usize, Option<usize>)> {
+    let mut positions = vec![];
+    for (book, shape) in books.iter().enumerate() {
+        for (chapter, length) in shape.chapters.iter().enumerate() {
+            if segments {
+                positions.extend(
+                    (1..=*length as usize).map(|segment| (book, chapter + 1, Some(segment))),
+                );
+            } else {
+                positions.push((book, chapter + 1, None));
+            }
+        }
+    }
+    positions
+}
+
+/// The `per_day` units learned on the `day`th day of a cycle through all of `units`.
+fn portion(
+    cycle: Cycle,
+    books: &[ShapeContents],
+    units: &[(usize, usize, Option<usize>)],
+    day: i64,
+    per_day: usize,
+) -> Result<Learning, anyhow::Error> {
+    if units.is_empty() {
+        return Err(anyhow!("There is nothing to learn for {}", cycle.name()));
+    }
+    let days = units.len().div_ceil(per_day) as i64;
+    let first = day.rem_euclid(days) as usize * per_day;
+    let refs = span_refs(books, &units[first..(first + per_day).min(units.len())]);
+    Ok(Learning {
+        cycle,
+        name: refs.join(", "),
+        refs,
+    })
+}
+
+/// Two mishnayot a day through the whole Mishnah.
+pub fn mishnah_yomit(date: NaiveDate, books: &[ShapeContents]) -> Result<Learning, anyhow::Error> {
+    let day = days_since(date, MISHNAH_YOMIT_START, "Mishnah Yomit")?;
+    portion(Cycle::Mishnah, books, &positions(books, true), day, 2)
+}
+
+/// One or three chapters a day through the Mishneh Torah, as the Rambam calendar divides it.
+pub fn rambam(date: NaiveDate, three: bool) -> Result<Learning, anyhow::Error> {
+    let day = days_since(date, RAMBAM_START, "Rambam")?;
+    let (cycle, per_day) = if three {
+        (Cycle::Rambam3, 3)
+    } else {
+        (Cycle::Rambam, 1)
+    };
+    let books: Vec<ShapeContents> = RAMBAM
+        .iter()
+        .map(|(title, chapters)| ShapeContents {
+            title: format!("Mishneh Torah, {}", title),
+            chapters: vec![1; *chapters],
+            ..ShapeContents::default()
+        })
+        .collect();
+    portion(cycle, &books, &positions(&books, false), day, per_day)
+}
+
+/// A chapter of Tanakh every day but Friday and Shabbat.
+pub fn nine_twenty_nine(
+    date: NaiveDate,
+    books: &[ShapeContents],
+) -> Result<Learning, anyhow::Error> {
+    let days = days_since(date, NINE_TWENTY_NINE_START, "929")?;
+    if matches!(date.weekday(), Weekday::Fri | Weekday::Sat) {
+        return Ok(Learning {
+            cycle: Cycle::Tanakh,
+            name: "No chapter on Friday or Shabbat".to_string(),
+            refs: vec![],
+        });
+    }
+    // The cycle started on a Sunday, so every week before this one had five days
+    let day = days / 7 * 5 + days % 7;
+    portion(Cycle::Tanakh, books, &positions(books, false), day, 1)
+}
+
+pub fn tehillim(date: NaiveDate) -> Learning {
+    let hebrew_date = HebrewDate::from_gregorian(date);
+    let short_month = HebrewDate {
+        day: 30,
+        ..hebrew_date
+    }
+    .to_gregorian()
+    .is_err();
+    let psalms = match hebrew_date.day {
+        29 if short_month => "140-150",
+        day => TEHILLIM[day as usize - 1],
+    };
+    let reference = format!("Psalms {}", psalms);
+    Learning {
+        cycle: Cycle::Tehillim,
+        name: reference.clone(),
+        refs: vec![reference],
+    }
+}
+
+/// What every cycle learns on `date`. The Mishnah and Tanakh are divided up by
+/// their shapes, which are only downloaded the first time.
+pub fn learning(
+    date: NaiveDate,
+    xdg_dirs: &xdg::BaseDirectories,
+    toc: &Toc,
+) -> Vec<(Cycle, Result<Learning, anyhow::Error>)> {
+    let shaped =
+        |parts: &[&str], find: &dyn Fn(&[ShapeContents]) -> Result<Learning, anyhow::Error>| {
+            parts_books(xdg_dirs, toc, parts).and_then(|books| find(&books))
+        };
+    vec![
+        (Cycle::Daf, daf_yomi(date)),
+        (Cycle::Yerushalmi, yerushalmi_yomi(date)),
+        (
+            Cycle::Mishnah,
+            shaped(&MISHNAH_SEDARIM, &|books| mishnah_yomit(date, books)),
+        ),
+        (Cycle::Rambam, rambam(date, false)),
+        (Cycle::Rambam3, rambam(date, true)),
+        (
+            Cycle::Tanakh,
+            shaped(&TANAKH_PARTS, &|books| nine_twenty_nine(date, books)),
+        ),
+        (Cycle::Tehillim, Ok(tehillim(date))),
+    ]
+}
+
+/// The cycles that have something Sefaria can show on the day, in the order they're listed.
+pub fn showable(learning: &[(Cycle, Result<Learning, anyhow::Error>)]) -> Vec<Cycle> {
+    learning
+        .iter()
+        .filter(|(_, found)| found.as_ref().is_ok_and(|found| !found.refs.is_empty()))
+        .map(|(cycle, _)| *cycle)
+        .collect()
+}
+
+/// Every cycle as a table, with errors in place of anything that couldn't be worked out. Only the
+/// cycles that can be shown are numbered.
+pub fn format_learning(
+    date: NaiveDate,
+    learning: &[(Cycle, Result<Learning, anyhow::Error>)],
+) -> String {
+    let mut table = vec![
+        format!(
+            "# {} ~ {}",
+            date.format("%A, %-d %B %Y"),
+            HebrewDate::from_gregorian(date)
+        ),
+        "|-:|:-|:-|".to_string(),
+        "|**#**|**Cycle**|**Today**|".to_string(),
+        "|-:|:-|:-|".to_string(),
+    ];
+    let showable = showable(learning);
+    for (cycle, found) in learning {
+        let today = match found {
+            Ok(found) => found.name.clone(),
+            Err(nar) => format!("*{}*", nar),
+        };
+        let number = showable
+            .iter()
+            .position(|shown| shown == cycle)
+            .map(|idx| (idx + 1).to_string())
+            .unwrap_or_default();
+        table.push(format!("|{}|{}|{}|", number, cycle.name(), today));
+    }
+    table.push("|-".to_string());
+    table.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn daf_yomi_cycles() {
+        assert_eq!(daf_yomi(date("2020-01-05")).unwrap().name, "Berakhot 2");
+        assert_eq!(daf_yomi(date("2012-08-03")).unwrap().name, "Berakhot 2");
+        assert_eq!(daf_yomi(date("2020-01-04")).unwrap().name, "Niddah 73");
+        assert_eq!(
+            daf_yomi(date("2020-03-08")).unwrap().refs,
+            ["Shabbat 2a-2b"]
+        );
+        assert!(daf_yomi(date("1900-01-01")).is_err());
+    }
+
+    #[test]
+    fn portions() {
+        let books: Vec<ShapeContents> = serde_json::from_str(
+            r#"[{"section": "", "heTitle": "", "title": "Mishnah Berakhot", "length": 2,
+                 "chapters": [3, 2], "book": "", "heBook": ""},
+                {"section": "", "heTitle": "", "title": "Mishnah Peah", "length": 1,
+                 "chapters": [2], "book": "", "heBook": ""}]"#,
+        )
+        .unwrap();
+        let segments = positions(&books, true);
+        let learned = |day| {
+            portion(Cycle::Mishnah, &books, &segments, day, 2)
+                .unwrap()
+                .refs
+        };
+        assert_eq!(learned(0), ["Mishnah Berakhot 1:1-2"]);
+        assert_eq!(learned(1), ["Mishnah Berakhot 1:3-2:1"]);
+        assert_eq!(learned(2), ["Mishnah Berakhot 2:2", "Mishnah Peah 1:1"]);
+        assert_eq!(learned(4), learned(0));
+        assert_eq!(tehillim(date("2024-12-26")).name, "Psalms 119:1-96");
+        assert_eq!(
+            mishnah_yomit(date("1947-05-20"), &books).unwrap().name,
+            "Mishnah Berakhot 1:1-2"
+        );
+    }
+
+    #[test]
+    fn rambam_cycles() {
+        let name = |day, three| rambam(date(day), three).unwrap().name;
+        assert_eq!(
+            name("1984-04-29", true),
+            "Mishneh Torah, Transmission of the Oral Law, Mishneh Torah, Positive Mitzvot, \
+             Mishneh Torah, Negative Mitzvot"
+        );
+        assert_eq!(
+            name("1984-05-05", true),
+            "Mishneh Torah, Foundations of the Torah 2-4"
+        );
+        // The first cycle of three chapters finished on 11 Nisan 5745
+        assert_eq!(
+            name("1985-04-02", true),
+            "Mishneh Torah, Kings and Wars 10-12"
+        );
+        assert_eq!(name("1985-04-03", true), name("1984-04-29", true));
+        assert_eq!(name("1984-05-01", false), "Mishneh Torah, Negative Mitzvot");
+        // Three cycles of three chapters take as long as one of a chapter
+        assert_eq!(
+            name("1987-02-09", false),
+            "Mishneh Torah, Kings and Wars 12"
+        );
+        assert_eq!(
+            name("1987-02-10", false),
+            "Mishneh Torah, Transmission of the Oral Law"
+        );
+        assert_eq!(name("1987-02-10", true), name("1984-04-29", true));
+    }
+
+    #[test]
+    fn nine_twenty_nine_cycles() {
+        // Tanakh/Torah, Tanakh/Prophets and Tanakh/Writings as their shapes have them
+        let books: Vec<ShapeContents> = [
+            ("Genesis", 50),
+            ("Exodus", 40),
+            ("Leviticus", 27),
+            ("Numbers", 36),
+            ("Deuteronomy", 34),
+            ("Joshua", 24),
+            ("Judges", 21),
+            ("I Samuel", 31),
+            ("II Samuel", 24),
+            ("I Kings", 22),
+            ("II Kings", 25),
+            ("Isaiah", 66),
+            ("Jeremiah", 52),
+            ("Ezekiel", 48),
+            ("Hosea", 14),
+            ("Joel", 4),
+            ("Amos", 9),
+            ("Obadiah", 1),
+            ("Jonah", 4),
+            ("Micah", 7),
+            ("Nahum", 3),
+            ("Habakkuk", 3),
+            ("Zephaniah", 3),
+            ("Haggai", 2),
+            ("Zechariah", 14),
+            ("Malachi", 3),
+            ("Psalms", 150),
+            ("Proverbs", 31),
+            ("Job", 42),
+            ("Song of Songs", 8),
+            ("Ruth", 4),
+            ("Lamentations", 5),
+            ("Ecclesiastes", 12),
+            ("Esther", 10),
+            ("Daniel", 12),
+            ("Ezra", 10),
+            ("Nehemiah", 13),
+            ("I Chronicles", 29),
+            ("II Chronicles", 36),
+        ]
+        .iter()
+        .map(|(title, chapters)| ShapeContents {
+            title: title.to_string(),
+            chapters: vec![1; *chapters],
+            ..ShapeContents::default()
+        })
+        .collect();
+        assert_eq!(positions(&books, false).len(), 929);
+        let name = |day| nine_twenty_nine(date(day), &books).unwrap().name;
+        assert_eq!(name("2014-12-21"), "Genesis 1");
+        assert_eq!(name("2014-12-26"), "No chapter on Friday or Shabbat");
+        assert_eq!(name("2014-12-28"), "Genesis 6");
+        assert_eq!(name("2015-09-07"), "Deuteronomy 34");
+        assert_eq!(name("2015-09-08"), "Joshua 1");
+        assert_eq!(name("2018-07-11"), "II Chronicles 36");
+        assert_eq!(name("2018-07-12"), "Genesis 1");
+    }
+}
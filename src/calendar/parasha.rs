@@ -0,0 +1,523 @@
+// The weekly parasha is worked out from the calendar: the cycle starts after Simchat Torah, skips
+// Shabbatot that are holidays, and combines pairs of parashiyot when there aren't enough
+// Shabbatot left before the next fixed point in the year.
+use anyhow::anyhow;
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+
+use super::hebrew_date::{is_leap_year, HebrewDate, Month};
+
+/// As Sefaria names them, so they can be searched with `Parashat`.
+pub static PARASHIYOT: [&str; 53] = [
+    "Bereshit",
+    "Noach",
+    "Lech Lecha",
+    "Vayera",
+    "Chayei Sara",
+    "Toldot",
+    "Vayetzei",
+    "Vayishlach",
+    "Vayeshev",
+    "Miketz",
+    "Vayigash",
+    "Vayechi",
+    "Shemot",
+    "Vaera",
+    "Bo",
+    "Beshalach",
+    "Yitro",
+    "Mishpatim",
+    "Terumah",
+    "Tetzaveh",
+    "Ki Tisa",
+    "Vayakhel",
+    "Pekudei",
+    "Vayikra",
+    "Tzav",
+    "Shmini",
+    "Tazria",
+    "Metzora",
+    "Achrei Mot",
+    "Kedoshim",
+    "Emor",
+    "Behar",
+    "Bechukotai",
+    "Bamidbar",
+    "Nasso",
+    "Beha'alotcha",
+    "Sh'lach",
+    "Korach",
+    "Chukat",
+    "Balak",
+    "Pinchas",
+    "Matot",
+    "Masei",
+    "Devarim",
+    "Vaetchanan",
+    "Eikev",
+    "Re'eh",
+    "Shoftim",
+    "Ki Teitzei",
+    "Ki Tavo",
+    "Nitzavim",
+    "Vayeilech",
+    "Ha'azinu",
+];
+
+/// The haftarah of every parasha, as Ashkenazim read it.
+static HAFTAROT: [&str; 53] = [
+    "Isaiah 42:5-43:10",
+    "Isaiah 54:1-55:5",
+    "Isaiah 40:27-41:16",
+    "II Kings 4:1-37",
+    "I Kings 1:1-31",
+    "Malachi 1:1-2:7",
+    "Hosea 12:13-14:10",
+    "Hosea 11:7-12:12",
+    "Amos 2:6-3:8",
+    "I Kings 3:15-4:1",
+    "Ezekiel 37:15-28",
+    "I Kings 2:1-12",
+    "Isaiah 27:6-28:13",
+    "Ezekiel 28:25-29:21",
+    "Jeremiah 46:13-28",
+    "Judges 4:4-5:31",
+    "Isaiah 6:1-7:6",
+    "Jeremiah 34:8-22",
+    "I Kings 5:26-6:13",
+    "Ezekiel 43:10-27",
+    "I Kings 18:1-39",
+    "I Kings 7:40-50",
+    "I Kings 7:51-8:21",
+    "Isaiah 43:21-44:23",
+    "Jeremiah 7:21-8:3",
+    "II Samuel 6:1-7:17",
+    "II Kings 4:42-5:19",
+    "II Kings 7:3-20",
+    "Ezekiel 22:1-16",
+    "Amos 9:7-15",
+    "Ezekiel 44:15-31",
+    "Jeremiah 32:6-27",
+    "Jeremiah 16:19-17:14",
+    "Hosea 2:1-22",
+    "Judges 13:2-25",
+    "Zechariah 2:14-4:7",
+    "Joshua 2:1-24",
+    "I Samuel 11:14-12:22",
+    "Judges 11:1-33",
+    "Micah 5:6-6:8",
+    "I Kings 18:46-19:21",
+    "Jeremiah 1:1-2:3",
+    "Jeremiah 2:4-28",
+    "Isaiah 1:1-27",
+    "Isaiah 40:1-26",
+    "Isaiah 49:14-51:3",
+    "Isaiah 54:11-55:5",
+    "Isaiah 51:12-52:12",
+    "Isaiah 54:1-10",
+    "Isaiah 60:1-22",
+    "Isaiah 61:10-63:9",
+    "Isaiah 55:6-56:8",
+    "II Samuel 22:1-51",
+];
+
+static VAYAKHEL: usize = 21;
+static TZAV: usize = 24;
+static TAZRIA: usize = 26;
+static ACHREI_MOT: usize = 28;
+static BEHAR: usize = 31;
+static BAMIDBAR: usize = 33;
+static CHUKAT: usize = 38;
+static MATOT: usize = 41;
+static DEVARIM: usize = 43;
+static NITZAVIM: usize = 50;
+static HAAZINU: usize = 52;
+
+// From the three Shabbatot before Tisha B'Av to the seven after it, each with what it's called
+static REBUKE_AND_CONSOLATION: [(&str, &str); 10] = [
+    ("Three of Rebuke", "Jeremiah 1:1-2:3"),
+    ("Three of Rebuke", "Jeremiah 2:4-28"),
+    ("Shabbat Chazon", "Isaiah 1:1-27"),
+    ("Shabbat Nachamu", "Isaiah 40:1-26"),
+    ("Seven of Consolation", "Isaiah 49:14-51:3"),
+    ("Seven of Consolation", "Isaiah 54:11-55:5"),
+    ("Seven of Consolation", "Isaiah 51:12-52:12"),
+    ("Seven of Consolation", "Isaiah 54:1-10"),
+    ("Seven of Consolation", "Isaiah 60:1-22"),
+    ("Seven of Consolation", "Isaiah 61:10-63:9"),
+];
+
+// Parashiyot that can be read together with the one after them, the first to be combined first
+static PAIRS: [usize; 7] = [MATOT, CHUKAT, TAZRIA, ACHREI_MOT, BEHAR, VAYAKHEL, NITZAVIM];
+
+/// The haftarah read on a Shabbat, along with what the Shabbat is called when its haftarah takes
+/// the place of the parasha's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Haftarah {
+    pub occasion: Option<&'static str>,
+    pub refs: Vec<&'static str>,
+}
+
+impl Haftarah {
+    fn special(occasion: &'static str, refs: &[&'static str]) -> Haftarah {
+        Haftarah {
+            occasion: Some(occasion),
+            refs: refs.to_vec(),
+        }
+    }
+}
+
+/// What is read on a Shabbat.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reading {
+    /// One parasha, or two read together
+    Parasha(Vec<usize>),
+    /// A holiday with its own reading instead
+    Holiday(&'static str),
+}
+
+impl Reading {
+    pub fn name(&self) -> String {
+        match self {
+            Reading::Parasha(parashiyot) => parashiyot
+                .iter()
+                .map(|parasha| PARASHIYOT[*parasha])
+                .collect::<Vec<_>>()
+                .join("-"),
+            Reading::Holiday(holiday) => holiday.to_string(),
+        }
+    }
+
+    /// The haftarah read with the parasha on `shabbat`. Unless the Shabbat has one of its own,
+    /// that's the haftarah of the later of two parashiyot read together, except for
+    /// Nitzavim-Vayeilech.
+    pub fn haftarah(&self, shabbat: NaiveDate) -> Option<Haftarah> {
+        let parashiyot = match self {
+            Reading::Parasha(parashiyot) => parashiyot,
+            Reading::Holiday(_) => return None,
+        };
+        if let Some(special) = special_haftarah(shabbat) {
+            return Some(special);
+        }
+        let parasha = if parashiyot.first() == Some(&NITZAVIM) {
+            NITZAVIM
+        } else {
+            *parashiyot.last()?
+        };
+        Some(Haftarah {
+            occasion: None,
+            refs: vec![HAFTAROT[parasha]],
+        })
+    }
+}
+
+/// Holidays whose reading replaces the parasha when they fall on Shabbat. Outside of Israel the
+/// first and last days of a festival are doubled.
+fn holiday(date: HebrewDate, israel: bool) -> Option<&'static str> {
+    Some(match (date.month, date.day) {
+        (Month::Tishri, 1 | 2) => "Rosh Hashana",
+        (Month::Tishri, 10) => "Yom Kippur",
+        (Month::Tishri, 15) => "Sukkot",
+        (Month::Tishri, 16) if !israel => "Sukkot",
+        (Month::Tishri, 16..=21) => "Shabbat Chol HaMoed Sukkot",
+        (Month::Tishri, 22) => "Shemini Atzeret",
+        (Month::Tishri, 23) if !israel => "Simchat Torah",
+        (Month::Nisan, 15) => "Pesach",
+        (Month::Nisan, 16) if !israel => "Pesach",
+        (Month::Nisan, 16..=20) => "Shabbat Chol HaMoed Pesach",
+        (Month::Nisan, 21) => "Pesach VII",
+        (Month::Nisan, 22) if !israel => "Pesach VIII",
+        (Month::Sivan, 6) => "Shavuot",
+        (Month::Sivan, 7) if !israel => "Shavuot II",
+        _ => return None,
+    })
+}
+
+/// The haftarah of a Shabbat that has its own, whatever the parasha is, in the order they take
+/// precedence over each other:
+/// * the three Shabbatot before Tisha B'Av and the seven after it
+/// * Shabbat Shuva, between Rosh Hashana and Yom Kippur
+/// * Shekalim, Zachor, Parah and HaChodesh, around Adar (Adar II in a leap year)
+/// * Chanukah, which Rosh Chodesh Tevet can fall in
+/// * Rosh Chodesh, and Machar Chodesh the day before it
+fn special_haftarah(shabbat: NaiveDate) -> Option<Haftarah> {
+    let date = HebrewDate::from_gregorian(shabbat);
+    let year = date.year;
+    let on = |month, day| gregorian(year, month, day).ok();
+
+    // Shabbat Chazon is the last one on or before Tisha B'Av
+    let tisha_bav = on(Month::Av, 9)?;
+    let chazon = tisha_bav - Days::new((tisha_bav.weekday().num_days_from_sunday() as u64 + 1) % 7);
+    let week = (shabbat - chazon).num_days() / 7 + 2;
+    if let Some((occasion, reference)) = usize::try_from(week)
+        .ok()
+        .and_then(|week| REBUKE_AND_CONSOLATION.get(week))
+    {
+        return Some(Haftarah::special(occasion, &[reference]));
+    }
+
+    if date.month == Month::Tishri && (3..=9).contains(&date.day) {
+        return Some(Haftarah::special(
+            "Shabbat Shuva",
+            &["Hosea 14:2-10", "Joel 2:15-27"],
+        ));
+    }
+
+    let adar = if is_leap_year(year) {
+        Month::AdarII
+    } else {
+        Month::Adar
+    };
+    // Shekalim and HaChodesh are read on or before the first of the month, Zachor before Purim
+    let before = |date: NaiveDate| next_shabbat(date - Days::new(6));
+    let rosh_chodesh_adar = on(adar, 1)?;
+    let purim = on(adar, 14)?;
+    let rosh_chodesh_nisan = on(Month::Nisan, 1)?;
+    let four = [
+        (
+            before(rosh_chodesh_adar),
+            "Shabbat Shekalim",
+            "II Kings 12:1-17",
+        ),
+        (
+            before(purim - Days::new(1)),
+            "Shabbat Zachor",
+            "I Samuel 15:2-34",
+        ),
+        (
+            before(rosh_chodesh_nisan) - Days::new(7),
+            "Shabbat Parah",
+            "Ezekiel 36:16-38",
+        ),
+        (
+            before(rosh_chodesh_nisan),
+            "Shabbat HaChodesh",
+            "Ezekiel 45:16-46:18",
+        ),
+    ];
+    if let Some((_, occasion, reference)) = four.iter().find(|(date, ..)| *date == shabbat) {
+        return Some(Haftarah::special(occasion, &[reference]));
+    }
+
+    // Chanukah starts on 25 Kislev of the Hebrew year that Tevet is in, which is this one
+    let chanukah = on(Month::Kislev, 25)?;
+    match (shabbat - chanukah).num_days() {
+        0..=6 => {
+            return Some(Haftarah::special(
+                "Shabbat Chanukah",
+                &["Zechariah 2:14-4:7"],
+            ))
+        }
+        // Only when Chanukah starts on Shabbat does it last until the next one
+        7 => {
+            return Some(Haftarah::special(
+                "Second Shabbat Chanukah",
+                &["I Kings 7:40-50"],
+            ))
+        }
+        _ => {}
+    }
+
+    match (date.month, date.day) {
+        (Month::Tishri, 1) | (Month::Elul, 29) => None,
+        (_, 1 | 30) => Some(Haftarah::special(
+            "Shabbat Rosh Chodesh",
+            &["Isaiah 66:1-24"],
+        )),
+        (_, 29) => Some(Haftarah::special("Machar Chodesh", &["I Samuel 20:18-42"])),
+        _ => None,
+    }
+}
+
+pub fn next_shabbat(date: NaiveDate) -> NaiveDate {
+    let days = (6 - date.weekday().num_days_from_sunday()) as u64;
+    date.checked_add_days(Days::new(days)).unwrap_or(date)
+}
+
+fn gregorian(year: i64, month: Month, day: i64) -> Result<NaiveDate, anyhow::Error> {
+    HebrewDate { year, month, day }.to_gregorian()
+}
+
+/// Every Shabbat of the cycle that starts after Simchat Torah in `year`, up to Sukkot of the
+/// next year, along with the parashiyot read on it. Pairs are combined in the order of `PAIRS`
+/// until each of these is read on the last Shabbat before:
+/// * Pesach, Tzav in years without a second Adar
+/// * Shavuot, Bamidbar outside of Israel, where a last day of Pesach on Shabbat can otherwise
+///   leave Israel a week ahead
+/// * the day after Tisha B'Av, Devarim
+/// * Sukkot, Ha'azinu, with Vayeilech on its own if there is a Shabbat for it after Rosh Hashana
+fn cycle(year: i64, israel: bool) -> Result<Vec<(NaiveDate, Reading)>, anyhow::Error> {
+    let start = gregorian(year, Month::Tishri, 22)?;
+    let end = gregorian(year + 1, Month::Tishri, 15)?;
+
+    let mut anchors = vec![];
+    if !is_leap_year(year) {
+        anchors.push((gregorian(year, Month::Nisan, 15)?, TZAV));
+    }
+    // A leap year that starts on Thursday has a Shabbat to spare, so Nasso comes first
+    let spare = is_leap_year(year) && gregorian(year, Month::Tishri, 1)?.weekday() == Weekday::Thu;
+    if !israel && !spare {
+        anchors.push((gregorian(year, Month::Sivan, 6)?, BAMIDBAR));
+    }
+    anchors.push((gregorian(year, Month::Av, 10)?, DEVARIM));
+    anchors.push((end, HAAZINU));
+
+    let mut shabbatot = next_shabbat(start.succ_opt().unwrap_or(start))
+        .iter_days()
+        .step_by(7)
+        .take_while(|date| *date < end)
+        .filter(|date| holiday(HebrewDate::from_gregorian(*date), israel).is_none())
+        .peekable();
+
+    let mut readings = vec![];
+    let mut next = 0;
+    for (limit, last) in anchors {
+        let mut days = vec![];
+        while let Some(date) = shabbatot.next_if(|date| *date < limit) {
+            days.push(date);
+        }
+        let combined = (last + 1 - next)
+            .checked_sub(days.len())
+            .ok_or(anyhow!("There are too many Shabbatot in {} to fit", year))?;
+        let pairs: Vec<usize> = PAIRS
+            .iter()
+            .copied()
+            .filter(|pair| (next..last).contains(pair) && !(israel && *pair == CHUKAT))
+            .take(combined)
+            .collect();
+        if pairs.len() < combined {
+            return Err(anyhow!("There are too few Shabbatot in {} to fit", year));
+        }
+
+        for date in days {
+            if pairs.contains(&next) {
+                readings.push((date, Reading::Parasha(vec![next, next + 1])));
+                next += 2;
+            } else {
+                readings.push((date, Reading::Parasha(vec![next])));
+                next += 1;
+            }
+        }
+    }
+    Ok(readings)
+}
+
+/// What is read on the Shabbat on or after `date`.
+pub fn parasha_for(date: NaiveDate, israel: bool) -> Result<(NaiveDate, Reading), anyhow::Error> {
+    let shabbat = next_shabbat(date);
+    let hebrew_date = HebrewDate::from_gregorian(shabbat);
+    if let Some(holiday) = holiday(hebrew_date, israel) {
+        return Ok((shabbat, Reading::Holiday(holiday)));
+    }
+    // Between Rosh Hashana and Sukkot the cycle is still the one from last year
+    for year in [hebrew_date.year - 1, hebrew_date.year] {
+        if let Some(found) = cycle(year, israel)?
+            .into_iter()
+            .find(|(found, _)| *found == shabbat)
+        {
+            return Ok(found);
+        }
+    }
+    Err(anyhow!("Could not find what is read on {}", shabbat))
+}
+
+/// The reading along with when it is and its haftarah.
+pub fn format_parasha(shabbat: NaiveDate, reading: &Reading, israel: bool) -> String {
+    let mut formatted_string = vec![
+        match reading {
+            Reading::Parasha(_) => format!("# Parashat {}", reading.name()),
+            Reading::Holiday(_) => format!("# {}", reading.name()),
+        },
+        format!(
+            "*Shabbat, {} ~ {} ~ {}*",
+            shabbat.format("%-d %B %Y"),
+            HebrewDate::from_gregorian(shabbat),
+            if israel { "Israel" } else { "Diaspora" }
+        ),
+    ];
+    match reading.haftarah(shabbat) {
+        Some(Haftarah {
+            occasion: Some(occasion),
+            refs,
+        }) => formatted_string.push(format!(
+            "**Haftarah for {}:** {}",
+            occasion,
+            refs.join(", ")
+        )),
+        Some(haftarah) => {
+            formatted_string.push(format!("**Haftarah:** {}", haftarah.refs.join(", ")))
+        }
+        None => formatted_string
+            .push("The holiday has its own reading instead of a parasha".to_string()),
+    }
+    formatted_string.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_on(date: &str, israel: bool) -> String {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        parasha_for(date, israel).unwrap().1.name()
+    }
+
+    #[test]
+    fn every_year_fits() {
+        for year in 5700..5900 {
+            for israel in [true, false] {
+                let readings = cycle(year, israel).unwrap();
+                assert_eq!(readings.last().unwrap().1.name(), "Ha'azinu");
+            }
+        }
+    }
+
+    #[test]
+    fn known_parashiyot() {
+        assert_eq!(read_on("2024-10-26", false), "Bereshit");
+        assert_eq!(read_on("2023-03-18", false), "Vayakhel-Pekudei");
+        // Rosh Hashana was on Thursday of a full year
+        assert_eq!(read_on("2025-03-20", false), "Vayakhel");
+        assert_eq!(read_on("2025-04-19", true), "Pesach VII");
+        // Pesach ended on Shabbat outside of Israel, which caught up with Matot-Masei
+        assert_eq!(read_on("2019-04-27", false), "Pesach VIII");
+        assert_eq!(read_on("2019-04-27", true), "Achrei Mot");
+        assert_eq!(read_on("2019-05-04", false), "Achrei Mot");
+        assert_eq!(read_on("2019-08-03", false), "Matot-Masei");
+        assert_eq!(read_on("2019-08-03", true), "Masei");
+    }
+
+    #[test]
+    fn special_haftarot() {
+        let haftarah = |date: &str| {
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+            let (shabbat, reading) = parasha_for(date, false).unwrap();
+            let haftarah = reading.haftarah(shabbat).unwrap();
+            (
+                haftarah.occasion.unwrap_or_default(),
+                haftarah.refs.join(", "),
+            )
+        };
+        assert_eq!(
+            haftarah("2024-01-06"),
+            ("", "Isaiah 27:6-28:13".to_string())
+        );
+        assert_eq!(haftarah("2024-10-05").0, "Shabbat Shuva");
+        // Shekalim on Rosh Chodesh Adar
+        assert_eq!(haftarah("2025-03-01").0, "Shabbat Shekalim");
+        assert_eq!(haftarah("2024-03-23").0, "Shabbat Zachor");
+        assert_eq!(haftarah("2024-03-30").0, "Shabbat Parah");
+        assert_eq!(haftarah("2024-04-06").0, "Shabbat HaChodesh");
+        // Rosh Chodesh Tevet during Chanukah
+        assert_eq!(haftarah("2022-12-24").0, "Shabbat Chanukah");
+        assert_eq!(haftarah("2024-11-02").0, "Shabbat Rosh Chodesh");
+        assert_eq!(haftarah("2023-10-14").0, "Machar Chodesh");
+        // Pinchas after the 17th of Tammuz
+        assert_eq!(
+            haftarah("2024-07-27"),
+            ("Three of Rebuke", "Jeremiah 1:1-2:3".to_string())
+        );
+        // Rosh Chodesh Av
+        assert_eq!(haftarah("2025-07-26").1, "Jeremiah 2:4-28");
+        assert_eq!(haftarah("2024-09-28").1, "Isaiah 61:10-63:9");
+    }
+}
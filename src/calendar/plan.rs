@@ -0,0 +1,225 @@
+// Study plans the user sets up themselves, split into daily portions when they're created so that
+// following them never has to go online for anything but the text.
+use anyhow::anyhow;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+
+use super::learning::{cycle_books, positions, span_refs};
+use crate::parser::shape::{category_shapes, ShapeContents};
+use crate::parser::toc::{find_category, resolve_path, Toc};
+
+const PLANS_FILE: &str = "plans.json";
+
+/// A book or category split into portions, one for every day from `start`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub name: String,
+    pub target: String,
+    pub start: NaiveDate,
+    pub portions: Vec<Vec<String>>,
+    // Indices of the portions that have been marked as learned
+    pub done: BTreeSet<usize>,
+}
+
+impl Plan {
+    /// The first portion that hasn't been learned yet.
+    pub fn next(&self) -> Option<usize> {
+        (0..self.portions.len()).find(|idx| !self.done.contains(idx))
+    }
+
+    /// How many portions should have been learned by the end of `date`.
+    pub fn due(&self, date: NaiveDate) -> usize {
+        ((date - self.start).num_days() + 1).clamp(0, self.portions.len() as i64) as usize
+    }
+
+    /// How far behind or ahead of the schedule the plan is on `date`.
+    pub fn status(&self, date: NaiveDate) -> String {
+        let due = self.due(date);
+        let done = self.done.len();
+        if done == self.portions.len() {
+            "Finished".to_string()
+        } else if date < self.start {
+            format!("Starts {}", self.start)
+        } else if done < due {
+            format!("{} behind", plural(due - done))
+        } else if done > due {
+            format!("{} ahead", plural(done - due))
+        } else {
+            "On track".to_string()
+        }
+    }
+}
+
+fn plural(days: usize) -> String {
+    match days {
+        1 => "1 day".to_string(),
+        days => format!("{} days", days),
+    }
+}
+
+pub fn load_plans(xdg_dirs: &xdg::BaseDirectories) -> Result<Vec<Plan>, anyhow::Error> {
+    match xdg_dirs.find_data_file(PLANS_FILE) {
+        Some(path) => Ok(serde_json::from_str(&fs::read_to_string(path)?)?),
+        None => Ok(vec![]),
+    }
+}
+
+pub fn save_plans(xdg_dirs: &xdg::BaseDirectories, plans: &[Plan]) -> Result<(), anyhow::Error> {
+    fs::write(
+        xdg_dirs.place_data_file(PLANS_FILE)?,
+        serde_json::to_string_pretty(plans)?,
+    )?;
+    Ok(())
+}
+
+/// The plan called `name`, or the only one there is when no name is given.
+pub fn find_plan<'a>(plans: &'a mut [Plan], name: &str) -> Result<&'a mut Plan, anyhow::Error> {
+    if name.is_empty() {
+        return match plans {
+            [plan] => Ok(plan),
+            [] => Err(anyhow!(
+                "There are no plans yet, make one with `rsbbi plan create`"
+            )),
+            _ => Err(anyhow!("There is more than one plan, say which one")),
+        };
+    }
+    plans
+        .iter_mut()
+        .find(|plan| plan.name.eq_ignore_ascii_case(name))
+        .ok_or(anyhow!("There is no plan called '{}'", name))
+}
+
+/// The books of a category, or just the book, that `target` names along with their shapes.
+fn target_books(
+    xdg_dirs: &xdg::BaseDirectories,
+    toc: &Toc,
+    target: &str,
+) -> Result<Vec<ShapeContents>, anyhow::Error> {
+    if find_category(toc, target).is_some() {
+        return cycle_books(xdg_dirs, toc, target);
+    }
+    let path = resolve_path(toc, target)?;
+    let (category, title) = path
+        .rsplit_once('/')
+        .ok_or(anyhow!("'{}' is not in any category", target))?;
+    category_shapes(xdg_dirs, category)?
+        .into_iter()
+        .find(|shape| shape.title == title)
+        .map(|shape| vec![shape])
+        .ok_or(anyhow!("Sefaria has no shape for {}", title))
+}
+
+/// Splits `units` into `days` portions as evenly as they go, or into portions of `per_day`.
+fn split<T: Clone>(units: &[T], days: Option<usize>, per_day: usize) -> Vec<Vec<T>> {
+    match days {
+        Some(days) => (0..days)
+            .map(|day| units[day * units.len() / days..(day + 1) * units.len() / days].to_vec())
+            .collect(),
+        None => units.chunks(per_day).map(|chunk| chunk.to_vec()).collect(),
+    }
+}
+
+/// A plan through every chapter of `target`, either finished in `days` or `per_day` chapters at
+/// a time.
+pub fn create_plan(
+    xdg_dirs: &xdg::BaseDirectories,
+    toc: &Toc,
+    name: &str,
+    target: &str,
+    start: NaiveDate,
+    days: Option<usize>,
+    per_day: usize,
+) -> Result<Plan, anyhow::Error> {
+    let books = target_books(xdg_dirs, toc, target)?;
+    // Talmud tractates leave the sections before `2a` empty
+    let chapters: Vec<_> = positions(&books, false)
+        .into_iter()
+        .filter(|(book, chapter, _)| books[*book].chapters[chapter - 1] > 0)
+        .collect();
+    match days {
+        Some(days) if days > chapters.len() => {
+            return Err(anyhow!(
+                "{} only has {} chapters, so it can't be spread over {} days",
+                target,
+                chapters.len(),
+                days
+            ))
+        }
+        Some(0) => return Err(anyhow!("A plan has to last at least a day")),
+        _ if chapters.is_empty() => return Err(anyhow!("There is nothing to learn in {}", target)),
+        _ => {}
+    }
+    Ok(Plan {
+        name: name.to_string(),
+        target: target.to_string(),
+        start,
+        portions: split(&chapters, days, per_day)
+            .iter()
+            .map(|portion| span_refs(&books, portion))
+            .collect(),
+        done: BTreeSet::new(),
+    })
+}
+
+/// Every plan as a numbered table with how it's going on `date`.
+pub fn format_plans(plans: &[Plan], date: NaiveDate) -> String {
+    if plans.is_empty() {
+        return "There are no plans yet, make one with `rsbbi plan create`".to_string();
+    }
+    let mut table = vec![
+        "|-:|:-|:-|:-|:-|".to_string(),
+        "|**#**|**Plan**|**Done**|**Next**|**Status**|".to_string(),
+        "|-:|:-|:-|:-|:-|".to_string(),
+    ];
+    for (idx, plan) in plans.iter().enumerate() {
+        let next = plan
+            .next()
+            .map(|next| plan.portions[next].join(", "))
+            .unwrap_or_default();
+        table.push(format!(
+            "|{}|{}|{}/{}|{}|{}|",
+            idx + 1,
+            plan.name,
+            plan.done.len(),
+            plan.portions.len(),
+            next,
+            plan.status(date)
+        ));
+    }
+    table.push("|-".to_string());
+    table.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_evenly() {
+        let units: Vec<usize> = (0..10).collect();
+        let lengths = |portions: Vec<Vec<usize>>| portions.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(lengths(split(&units, Some(3), 1)), [3, 3, 4]);
+        assert_eq!(lengths(split(&units, None, 4)), [4, 4, 2]);
+        assert_eq!(split(&units, Some(10), 1).concat(), units);
+    }
+
+    #[test]
+    fn tracks_progress() {
+        let mut plan = Plan {
+            name: "Berakhot".to_string(),
+            target: "Mishnah Berakhot".to_string(),
+            start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            portions: vec![vec!["Mishnah Berakhot 1".to_string()]; 9],
+            done: BTreeSet::new(),
+        };
+        let day = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        assert_eq!(plan.status(day(3)), "3 days behind");
+        plan.done.extend([0, 1, 2]);
+        assert_eq!(plan.status(day(3)), "On track");
+        assert_eq!(plan.status(day(2)), "1 day ahead");
+        plan.done.remove(&1);
+        assert_eq!(plan.next(), Some(1));
+    }
+}
@@ -0,0 +1,753 @@
+use anyhow::anyhow;
+use std::collections::HashSet;
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use termimad::MadSkin;
+
+use crate::calendar::hebrew_date::{format_date, parse_date};
+use crate::calendar::learning::{format_learning, learning, showable};
+use crate::calendar::parasha::{format_parasha, parasha_for, Reading};
+use crate::calendar::plan::{create_plan, find_plan, format_plans, load_plans, save_plans};
+use crate::common::history::{format_history, load_history, parse_since, record_visit};
+use crate::common::marks::{canonical_ref, edit_note, format_marks, load_marks, save_marks, Mark};
+use crate::common::pager::print_paged;
+use crate::common::state::LastViewed;
+use crate::ferror;
+use crate::parser::alt::{format_alt, resolve_alt};
+use crate::parser::args::{Commands, IndexAction, NoteAction, PlanAction};
+use crate::parser::bible_verse::parse_verse;
+use crate::parser::grep::{build_regex, grep_books};
+use crate::parser::identify::{format_candidates, identify};
+use crate::parser::index::LocalIndex;
+use crate::parser::info::{category_info, handle_info};
+use crate::parser::keyword::{
+    dedup_versions, format_expanded_hits, format_facets, format_hits, search_wrapper, Query,
+};
+use crate::parser::library::{cached_books, cached_titles, download_book, save_book};
+use crate::parser::metadata::{download_index_record, download_versions, format_card};
+use crate::parser::scan::{
+    find_citations, format_list, inline_citations, link_citations, ScanOutput,
+};
+use crate::parser::search::{download_passage, search_book, search_passage, Language, Passage};
+use crate::parser::shape::{shape_download, Shape};
+use crate::parser::toc::{books, default_toc, find_category, in_path, resolve_path};
+use crate::reader;
+use crate::shell;
+
+/// Everything a command needs, set up once so the shell can run many commands with it.
+pub struct Context {
+    pub xdg_dirs: xdg::BaseDirectories,
+    pub skin: MadSkin,
+    pub state_path: PathBuf,
+    pub parameters: Vec<(&'static str, &'static str)>,
+    // Used whenever `--hebrew` isn't passed, the shell can change it with `lang`
+    pub language: Language,
+    pub in_shell: bool,
+}
+
+impl Context {
+    pub fn new(xdg_dirs: xdg::BaseDirectories, skin: MadSkin) -> Result<Context, anyhow::Error> {
+        let state_path = xdg_dirs.place_state_file("last.json")?;
+        Ok(Context {
+            xdg_dirs,
+            skin,
+            state_path,
+            parameters: vec![("commentary", "0"), ("stripItags", "1"), ("context", "0")],
+            language: Language::English,
+            in_shell: false,
+        })
+    }
+
+    fn language(&self, hebrew: bool) -> Language {
+        if hebrew {
+            Language::Hebrew
+        } else {
+            self.language
+        }
+    }
+
+    /// Shows every ref, one after the other, remembering the passage when there is only one.
+    fn show_refs(
+        &self,
+        refs: &[String],
+        lines: bool,
+        language: Language,
+    ) -> Result<(), anyhow::Error> {
+        if let [reference] = refs {
+            self.show_passage(search_passage(
+                reference,
+                self.parameters.clone(),
+                lines,
+                language,
+                &self.marks(),
+            )?);
+            return Ok(());
+        }
+        let marks = self.marks();
+        let mut passages = vec![];
+        for reference in refs {
+            passages.push(
+                search_passage(reference, self.parameters.clone(), lines, language, &marks)?.text,
+            );
+        }
+        print_paged(&self.skin, &passages.join("\n---\n"));
+        Ok(())
+    }
+
+    /// Every bookmark, so that search can show their notes under the verses they're on.
+    fn marks(&self) -> Vec<Mark> {
+        load_marks(&self.xdg_dirs).unwrap_or_else(|nar| {
+            ferror!("Could not load notes: {}", nar);
+            vec![]
+        })
+    }
+
+    /// Prints a passage and remembers it so that `next` and `prev` can continue from it, and in
+    /// the history.
+    fn show_passage(&self, passage: Passage) {
+        print_paged(&self.skin, &passage.text);
+        if let Err(nar) = LastViewed::from(&passage).save(&self.state_path) {
+            ferror!("Could not remember last viewed passage: {}", nar);
+        }
+        if let Err(nar) = record_visit(&self.xdg_dirs, &passage) {
+            ferror!("Could not add passage to history: {}", nar);
+        }
+    }
+}
+
+pub fn run_command(cmd: &Commands, context: &mut Context) -> Result<(), anyhow::Error> {
+    let parameters = context.parameters.clone();
+    match cmd {
+        Commands::Search {
+            lines,
+            hebrew,
+            chapters,
+            next,
+            prev,
+            rest,
+        } => {
+            // Named parts like `Parashat Noach` are searched as the ref they cover
+            let spaced_rest =
+                resolve_alt(&context.xdg_dirs, &rest.join(" "))?.unwrap_or_else(|| rest.join(" "));
+            let parsed_verse = parse_verse(&spaced_rest)?;
+            let language = context.language(*hebrew);
+            let marks = context.marks();
+
+            let passage = if parsed_verse.section.is_none() && parsed_verse.verse.is_none() {
+                search_book(
+                    &parsed_verse.book,
+                    chapters.as_deref(),
+                    parameters.clone(),
+                    *lines,
+                    language,
+                    &marks,
+                )?
+            } else if chapters.is_some() {
+                return Err(anyhow!(
+                    "'--chapters' can only be used when searching a whole book"
+                ));
+            } else {
+                search_passage(&spaced_rest, parameters.clone(), *lines, language, &marks)?
+            };
+
+            let adjacent = match (next, prev) {
+                (true, _) => passage.next.clone(),
+                (_, true) => passage.prev.clone(),
+                _ => {
+                    context.show_passage(passage);
+                    return Ok(());
+                }
+            };
+            match adjacent {
+                Some(reference) => context.show_passage(search_passage(
+                    &reference, parameters, *lines, language, &marks,
+                )?),
+                None => {
+                    return Err(anyhow!(
+                        "There is no passage {} {}",
+                        if *next { "after" } else { "before" },
+                        passage.reference
+                    ))
+                }
+            }
+        }
+        Commands::Next { lines, hebrew } | Commands::Prev { lines, hebrew } => {
+            let is_next = matches!(cmd, Commands::Next { .. });
+            let last_viewed = LastViewed::load(&context.state_path).ok_or(anyhow!(
+                "Nothing has been viewed yet, so there is nothing to continue from"
+            ))?;
+
+            let adjacent = if is_next {
+                last_viewed.next
+            } else {
+                last_viewed.prev
+            };
+            match adjacent {
+                Some(reference) => context.show_passage(search_passage(
+                    &reference,
+                    parameters,
+                    *lines,
+                    context.language(*hebrew),
+                    &context.marks(),
+                )?),
+                None => {
+                    return Err(anyhow!(
+                        "There is no passage {} {}",
+                        if is_next { "after" } else { "before" },
+                        last_viewed.reference
+                    ))
+                }
+            }
+        }
+        Commands::History { since, count } => {
+            let mut history = load_history(&context.xdg_dirs)?;
+            if let Some(since) = since {
+                let since = parse_since(since, chrono::Local::now())?;
+                history.retain(|visit| visit.time >= since);
+            }
+            context.skin.print_text(&format_history(&history, *count));
+        }
+        Commands::Resume { lines, hebrew } => {
+            let visit = load_history(&context.xdg_dirs)?
+                .pop()
+                .ok_or(anyhow!("Nothing has been viewed yet"))?;
+            let language = match visit.language.as_str() {
+                _ if *hebrew => Language::Hebrew,
+                "he" => Language::Hebrew,
+                "bi" => Language::Bilingual,
+                _ => Language::English,
+            };
+            context.show_passage(search_passage(
+                &visit.reference,
+                parameters,
+                *lines,
+                language,
+                &context.marks(),
+            )?);
+        }
+        Commands::Read {
+            lines,
+            hebrew,
+            bilingual,
+            rest,
+        } => {
+            let language = if *bilingual {
+                Language::Bilingual
+            } else {
+                context.language(*hebrew)
+            };
+            reader::read(
+                &resolve_alt(&context.xdg_dirs, &rest.join(" "))?.unwrap_or_else(|| rest.join(" ")),
+                &context.skin,
+                &context.xdg_dirs,
+                parameters,
+                &context.state_path,
+                *lines,
+                language,
+            )?;
+        }
+        Commands::Keyword {
+            size,
+            page,
+            from,
+            all,
+            within,
+            exclude,
+            facets,
+            chart,
+            depth,
+            mode,
+            slop,
+            expand,
+            context: context_verses,
+            lines,
+            sort,
+            show_score,
+            versions,
+            local,
+            rest,
+        } => {
+            let mut query = Query::with_mode(&rest.join(" "), *size, *mode, *slop);
+            query.sort(*sort);
+            query.start = match (page, from) {
+                (Some(page), _) => (page - 1) * *size as i64,
+                (_, Some(from)) => *from,
+                _ => 0,
+            };
+
+            let mut excluded = vec![];
+            if !within.is_empty() || !exclude.is_empty() {
+                let toc = default_toc()?;
+                for name in within {
+                    query.filter(resolve_path(&toc, name)?);
+                }
+                for name in exclude {
+                    excluded.push(resolve_path(&toc, name)?);
+                }
+            }
+
+            let index = if *local {
+                Some(LocalIndex::load(&context.xdg_dirs)?)
+            } else {
+                None
+            };
+            let search = |query: &Query| match &index {
+                Some(index) => index.search(query),
+                None => search_wrapper(query, parameters.clone()),
+            };
+
+            if *facets {
+                // Only the counts are wanted, not any of the hits
+                query.size = 0;
+                query.aggs.push("path".to_string());
+                let mut results = search(&query)?;
+                results
+                    .aggregations
+                    .path
+                    .buckets
+                    .retain(|bucket| !excluded.iter().any(|path| in_path(&bucket.key, path)));
+                context
+                    .skin
+                    .print_text(&format_facets(&results, *depth as usize, *chart));
+                return Ok(());
+            }
+
+            // Refs already shown, so other versions of them are skipped on later pages too
+            let mut seen = HashSet::new();
+            loop {
+                let mut results = search(&query)?;
+                let page_length = results.hits.hits.len();
+                // The search-wrapper has no way to exclude anything, so it's done here
+                results
+                    .hits
+                    .hits
+                    .retain(|hit| !excluded.iter().any(|path| in_path(&hit.source.path, path)));
+                if !*versions {
+                    dedup_versions(&mut results.hits.hits, &mut seen);
+                }
+                let hidden = page_length - results.hits.hits.len();
+                context.skin.print_text(&if *expand {
+                    format_expanded_hits(
+                        &results,
+                        query.start,
+                        hidden,
+                        *context_verses,
+                        *lines,
+                        *show_score,
+                        parameters.clone(),
+                    )
+                } else {
+                    format_hits(&results, query.start, hidden, *show_score)
+                });
+
+                query.start += page_length as i64;
+                if !*all || page_length == 0 || query.start >= results.hits.total {
+                    break;
+                }
+            }
+        }
+        Commands::Info { alt, book } => {
+            let name = book.join(" ");
+            if let Some(alt) = alt {
+                let record = download_index_record(&name)?;
+                context.skin.print_text(&format_alt(&record, alt)?);
+                return Ok(());
+            }
+            let toc = default_toc()?;
+            if let Some((path, category)) = find_category(&toc, &name) {
+                let shapes: Shape = shape_download(
+                    format!(
+                        "https://www.sefaria.org/api/shape/{}",
+                        urlencoding::encode(&path)
+                    )
+                    .as_str(),
+                    [("", "")].to_vec(),
+                )
+                .map_err(|err| anyhow!("Could not get shape of category: {}: {}", path, err))?;
+                context.skin.print_text(&category_info(
+                    &path,
+                    &books(&category.contents),
+                    &shapes,
+                    &cached_titles(&context.xdg_dirs),
+                ));
+                return Ok(());
+            }
+
+            let parsed_verse = parse_verse(&name)?;
+            let raw_index: Shape = shape_download(
+                format!(
+                    "https://www.sefaria.org/api/shape/{}",
+                    urlencoding::encode(&parsed_verse.book)
+                )
+                .as_str(),
+                [("", "")].to_vec(),
+            )
+            .map_err(|err| {
+                anyhow!(
+                    "Could not get response with book: {}: {}",
+                    &parsed_verse.book,
+                    err
+                )
+            })?;
+
+            let whole_book = parsed_verse.section.is_none() && parsed_verse.verse.is_none();
+            let record = download_index_record(&parsed_verse.book);
+            let passage = if whole_book {
+                None
+            } else {
+                download_passage(&name, parameters).ok()
+            };
+            let mut info = handle_info(
+                &raw_index,
+                &parsed_verse,
+                passage.as_ref(),
+                record.as_ref().ok(),
+            )?;
+            // A whole book also gets everything its index record says about it
+            if whole_book {
+                match record {
+                    Ok(record) => {
+                        let versions = download_versions(&record.title).unwrap_or_else(|nar| {
+                            ferror!("Could not get versions of {}: {}", record.title, nar);
+                            vec![]
+                        });
+                        info = format!("{}\n---\n{}", format_card(&record, &versions), info);
+                    }
+                    Err(nar) => {
+                        ferror!(
+                            "Could not get index record of {}: {}",
+                            parsed_verse.book,
+                            nar
+                        );
+                    }
+                }
+            }
+            context.skin.print_text(&info);
+        }
+        Commands::Date { date } => {
+            context
+                .skin
+                .print_text(&format_date(parse_date(&date.join(" "))?));
+        }
+        Commands::Parasha {
+            date,
+            israel,
+            diaspora: _,
+            show,
+            lines,
+            hebrew,
+        } => {
+            let (shabbat, reading) =
+                parasha_for(parse_date(date.as_deref().unwrap_or_default())?, *israel)?;
+            let mut formatted_string = vec![format_parasha(shabbat, &reading, *israel)];
+            if *show {
+                let language = context.language(*hebrew);
+                let marks = context.marks();
+                if let Reading::Parasha(_) = reading {
+                    let reference =
+                        resolve_alt(&context.xdg_dirs, &format!("Parashat {}", reading.name()))?
+                            .ok_or(anyhow!("Could not find Parashat {}", reading.name()))?;
+                    let passage =
+                        search_passage(&reference, parameters.clone(), *lines, language, &marks)?;
+                    formatted_string.push(format!("---\n{}", passage.text));
+                }
+                if let Some(haftarah) = reading.haftarah(shabbat) {
+                    formatted_string.push(match haftarah.occasion {
+                        Some(occasion) => format!("---\n## Haftarah for {}", occasion),
+                        None => "---\n## Haftarah".to_string(),
+                    });
+                    for reference in haftarah.refs {
+                        let passage = search_passage(
+                            reference,
+                            parameters.clone(),
+                            *lines,
+                            language,
+                            &marks,
+                        )?;
+                        formatted_string.push(passage.text);
+                    }
+                }
+            }
+            print_paged(&context.skin, &formatted_string.join("\n"));
+        }
+        Commands::Today {
+            date,
+            show,
+            lines,
+            hebrew,
+        } => {
+            let date = parse_date(date.as_deref().unwrap_or_default())?;
+            let learning = learning(date, &context.xdg_dirs, &default_toc()?);
+            context.skin.print_text(&format_learning(date, &learning));
+
+            let cycle = match show {
+                Some(cycle) => *cycle,
+                None if std::io::stdin().is_terminal() => {
+                    let showable = showable(&learning);
+                    if showable.is_empty() {
+                        return Ok(());
+                    }
+                    println!("Show which? (1-{}, or nothing)", showable.len());
+                    let mut answer = String::new();
+                    std::io::stdin().read_line(&mut answer)?;
+                    match answer.trim().parse::<usize>() {
+                        Ok(idx) if (1..=showable.len()).contains(&idx) => showable[idx - 1],
+                        _ => return Ok(()),
+                    }
+                }
+                None => return Ok(()),
+            };
+            let found = learning
+                .into_iter()
+                .find(|(found, _)| *found == cycle)
+                .map(|(_, found)| found)
+                .ok_or(anyhow!("{} is not a cycle", cycle.name()))??;
+            if found.refs.is_empty() {
+                return Err(anyhow!("Sefaria has nothing to show for {}", found.name));
+            }
+            context.show_refs(&found.refs, *lines, context.language(*hebrew))?;
+        }
+        Commands::Plan { action } => {
+            let mut plans = load_plans(&context.xdg_dirs)?;
+            match action {
+                PlanAction::Create {
+                    days,
+                    per_day,
+                    name,
+                    start,
+                    target,
+                } => {
+                    let target = target.join(" ");
+                    let name = name.clone().unwrap_or_else(|| target.clone());
+                    if plans
+                        .iter()
+                        .any(|plan| plan.name.eq_ignore_ascii_case(&name))
+                    {
+                        return Err(anyhow!("There is already a plan called '{}'", name));
+                    }
+                    let plan = create_plan(
+                        &context.xdg_dirs,
+                        &default_toc()?,
+                        &name,
+                        &target,
+                        parse_date(start.as_deref().unwrap_or_default())?,
+                        days.map(|days| days as usize),
+                        *per_day as usize,
+                    )?;
+                    println!(
+                        "Made '{}', {} portions from {} to {}",
+                        plan.name,
+                        plan.portions.len(),
+                        plan.start,
+                        plan.start + chrono::Days::new(plan.portions.len() as u64 - 1)
+                    );
+                    plans.push(plan);
+                }
+                PlanAction::List => {
+                    let today = parse_date("")?;
+                    context.skin.print_text(&format_plans(&plans, today));
+                    return Ok(());
+                }
+                PlanAction::Next {
+                    lines,
+                    hebrew,
+                    plan,
+                } => {
+                    let plan = find_plan(&mut plans, &plan.join(" "))?;
+                    let next = plan.next().ok_or(anyhow!("'{}' is finished", plan.name))?;
+                    context.skin.print_text(&format!(
+                        "# {}, day {} of {}\n{}",
+                        plan.name,
+                        next + 1,
+                        plan.portions.len(),
+                        plan.status(parse_date("")?)
+                    ));
+                    context.show_refs(&plan.portions[next], *lines, context.language(*hebrew))?;
+                    return Ok(());
+                }
+                PlanAction::Mark { day, undo, plan } => {
+                    let plan = find_plan(&mut plans, &plan.join(" "))?;
+                    let idx = match (day, undo) {
+                        (Some(day), _) if *day as usize > plan.portions.len() => {
+                            return Err(anyhow!(
+                                "'{}' only has {} days",
+                                plan.name,
+                                plan.portions.len()
+                            ))
+                        }
+                        (Some(day), _) => *day as usize - 1,
+                        // Undoing goes back to the last portion learned
+                        (None, true) => *plan
+                            .done
+                            .last()
+                            .ok_or(anyhow!("Nothing in '{}' is marked yet", plan.name))?,
+                        (None, false) => plan
+                            .next()
+                            .ok_or(anyhow!("'{}' is already finished", plan.name))?,
+                    };
+                    if *undo {
+                        plan.done.remove(&idx);
+                    } else {
+                        plan.done.insert(idx);
+                    }
+                    println!(
+                        "{} {}, {}",
+                        if *undo { "Unmarked" } else { "Marked" },
+                        plan.portions[idx].join(", "),
+                        plan.status(parse_date("")?)
+                    );
+                }
+                PlanAction::Remove { plan } => {
+                    let name = find_plan(&mut plans, &plan.join(" "))?.name.clone();
+                    plans.retain(|plan| plan.name != name);
+                    println!("Removed '{}'", name);
+                }
+            }
+            save_plans(&context.xdg_dirs, &plans)?;
+        }
+        Commands::Mark {
+            note,
+            tags,
+            remove,
+            rest,
+        } => {
+            let reference = canonical_ref(&default_toc()?, &rest.join(" "))?;
+            let mut marks = load_marks(&context.xdg_dirs)?;
+            let found = marks.iter().position(|mark| mark.reference == reference);
+            match (found, remove) {
+                (Some(idx), true) => {
+                    marks.remove(idx);
+                    println!("Removed {}", reference);
+                }
+                (None, true) => return Err(anyhow!("{} isn't marked", reference)),
+                (Some(idx), false) => {
+                    if let Some(note) = note {
+                        marks[idx].note = note.trim().to_string();
+                    }
+                    marks[idx].tags.extend(tags.iter().cloned());
+                    println!("Updated {}", reference);
+                }
+                (None, false) => {
+                    marks.push(Mark {
+                        reference: reference.clone(),
+                        note: note.as_deref().unwrap_or_default().trim().to_string(),
+                        tags: tags.iter().cloned().collect(),
+                        added: parse_date("")?,
+                    });
+                    println!("Marked {}", reference);
+                }
+            }
+            save_marks(&context.xdg_dirs, &marks)?;
+        }
+        Commands::Marks { tag } => {
+            let marks = load_marks(&context.xdg_dirs)?;
+            context
+                .skin
+                .print_text(&format_marks(&marks, tag.as_deref()));
+        }
+        Commands::Note { action } => {
+            let NoteAction::Edit { rest } = action;
+            let reference = canonical_ref(&default_toc()?, &rest.join(" "))?;
+            let mut marks = load_marks(&context.xdg_dirs)?;
+            let idx = match marks.iter().position(|mark| mark.reference == reference) {
+                Some(idx) => idx,
+                None => {
+                    marks.push(Mark {
+                        reference: reference.clone(),
+                        added: parse_date("")?,
+                        ..Mark::default()
+                    });
+                    marks.len() - 1
+                }
+            };
+            marks[idx].note = edit_note(&marks[idx].note)?;
+            save_marks(&context.xdg_dirs, &marks)?;
+            println!("Saved the note on {}", reference);
+        }
+        Commands::Identify { candidates, rest } => {
+            let quotation = if rest.is_empty() {
+                let mut quotation = String::new();
+                std::io::stdin().read_to_string(&mut quotation)?;
+                quotation
+            } else {
+                rest.join(" ")
+            };
+            context.skin.print_text(&format_candidates(
+                &identify(&quotation, parameters)?,
+                *candidates,
+            ));
+        }
+        Commands::Scan {
+            output,
+            hebrew,
+            file,
+        } => {
+            let text = std::fs::read_to_string(file)
+                .map_err(|err| anyhow!("Could not read {}: {}", file.display(), err))?;
+            let citations = find_citations(&text, &default_toc()?);
+            // These are printed as they are, so they can be saved or piped elsewhere
+            println!(
+                "{}",
+                match output {
+                    ScanOutput::List => format_list(&citations),
+                    ScanOutput::Links => link_citations(&text, &citations),
+                    ScanOutput::Inline =>
+                        inline_citations(&text, &citations, parameters, context.language(*hebrew)),
+                }
+            );
+        }
+        Commands::Grep {
+            within,
+            ignore_case,
+            ignore_nikkud,
+            root,
+            pattern,
+        } => {
+            let regex = build_regex(pattern, *root, *ignore_nikkud, *ignore_case)?;
+            let mut books = cached_books(&context.xdg_dirs)?;
+            if !within.is_empty() {
+                let toc = default_toc()?;
+                let paths = within
+                    .iter()
+                    .map(|name| resolve_path(&toc, name))
+                    .collect::<Result<Vec<_>, _>>()?;
+                books.retain(|book| paths.iter().any(|path| in_path(&book.path, path)));
+            }
+            if books.is_empty() {
+                return Err(anyhow!(
+                    "No books to search have been downloaded, add some with `rsbbi index add`"
+                ));
+            }
+            print_paged(
+                &context.skin,
+                &grep_books(&books, &regex, *ignore_nikkud, *root),
+            );
+        }
+        Commands::Index { action } => {
+            let toc = default_toc()?;
+            if let IndexAction::Add { books } = action {
+                for book in books {
+                    println!("Downloading {}", book);
+                    save_book(
+                        &context.xdg_dirs,
+                        &download_book(book, &toc, parameters.clone())?,
+                    )?;
+                }
+            }
+            let index = LocalIndex::build(cached_books(&context.xdg_dirs)?, &toc);
+            index.save(&context.xdg_dirs)?;
+            println!(
+                "Indexed {} segments in {} words",
+                index.documents.len(),
+                index.terms.len()
+            );
+        }
+        Commands::Shell => {
+            if context.in_shell {
+                return Err(anyhow!("Already in the shell"));
+            }
+            shell::run(context)?;
+        }
+    }
+    Ok(())
+}
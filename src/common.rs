@@ -1,2 +1,8 @@
 pub mod download_json;
 pub mod ferror;
+#[cfg(test)]
+pub mod fixtures;
+pub mod history;
+pub mod marks;
+pub mod pager;
+pub mod state;
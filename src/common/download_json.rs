@@ -1,38 +1,61 @@
 use crate::parser::keyword::Root;
-use reqwest::{self, header::USER_AGENT};
+use anyhow::anyhow;
+use reqwest::{self, blocking::Client, header::USER_AGENT};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
 
-pub fn download(url: &str, parameters: Vec<(&str, &str)>) -> Value {
-    let client = reqwest::blocking::Client::new();
-    let response = client
+static CLIENT: OnceLock<Client> = OnceLock::new();
+// Responses by URL and query, so that the shell never downloads the same thing twice
+static CACHE: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+/// The one HTTP client every request goes through.
+pub fn client() -> &'static Client {
+    CLIENT.get_or_init(Client::new)
+}
+
+fn user_agent() -> String {
+    format!("RSBBI (gh:Elsie19/rsbbi) v{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Sends a GET request, or returns the response from the last time it was sent.
+pub fn try_get_text(url: &str, parameters: Vec<(&str, &str)>) -> Result<String, reqwest::Error> {
+    let key = format!("{}?{:?}", url, parameters);
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(response) = cache.lock().unwrap().get(&key) {
+        return Ok(response.clone());
+    }
+
+    let response = client()
         .get(url)
         .query(&parameters)
-        .header(
-            USER_AGENT,
-            format!("RSBBI (gh:Elsie19/rsbbi) v{}", env!("CARGO_PKG_VERSION")),
-        )
-        .send()
-        .unwrap()
-        .text()
-        .unwrap();
+        .header(USER_AGENT, user_agent())
+        .send()?
+        .text()?;
+
+    cache.lock().unwrap().insert(key, response.clone());
+    Ok(response)
+}
 
-    serde_json::from_str(&response).unwrap()
+pub fn download(url: &str, parameters: Vec<(&str, &str)>) -> Result<Value, anyhow::Error> {
+    let text =
+        try_get_text(url, parameters).map_err(|nar| anyhow!("Could not reach Sefaria: {}", nar))?;
+    Ok(serde_json::from_str(&text)?)
 }
 
-pub fn post_download(url: &str, body: String, parameters: Vec<(&str, &str)>) -> Root {
-    let client = reqwest::blocking::Client::new();
-    let response = client
+pub fn post_download(
+    url: &str,
+    body: String,
+    parameters: Vec<(&str, &str)>,
+) -> Result<Root, anyhow::Error> {
+    let response = client()
         .post(url)
         .query(&parameters)
         .body(body)
-        .header(
-            USER_AGENT,
-            format!("RSBBI (gh:Elsie19/rsbbi) v{}", env!("CARGO_PKG_VERSION")),
-        )
+        .header(USER_AGENT, user_agent())
         .send()
-        .unwrap()
-        .text()
-        .unwrap();
+        .and_then(|response| response.text())
+        .map_err(|nar| anyhow!("Could not reach Sefaria: {}", nar))?;
 
-    serde_json::from_str(&response).unwrap()
+    Ok(serde_json::from_str(&response)?)
 }
@@ -0,0 +1,30 @@
+// Data shared between the tests of different modules.
+use crate::parser::toc::Toc;
+
+/// A few categories and books of the table of contents.
+pub fn toc() -> Toc {
+    serde_json::from_str(
+        r#"[
+            {"category": "Tanakh", "contents": [
+                {"category": "Torah", "contents": [
+                    {"title": "Genesis", "categories": ["Tanakh", "Torah"]}
+                ]},
+                {"category": "Prophets", "contents": [
+                    {"title": "I Samuel", "categories": ["Tanakh", "Prophets"]}
+                ]},
+                {"category": "Writings", "contents": [
+                    {"title": "Song of Songs", "categories": ["Tanakh", "Writings"]}
+                ]}
+            ]},
+            {"category": "Talmud", "contents": [
+                {"category": "Bavli", "contents": [
+                    {"title": "Berakhot", "categories": ["Talmud", "Bavli"]}
+                ]}
+            ]},
+            {"category": "Halakhah", "contents": [
+                {"category": "Mishneh Torah", "contents": []}
+            ]}
+        ]"#,
+    )
+    .unwrap()
+}
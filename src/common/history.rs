@@ -0,0 +1,157 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Days, Local, NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+use crate::calendar::hebrew_date::parse_date;
+use crate::parser::search::Passage;
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// A passage that was shown, kept one to a line so that recording it only ever appends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Visit {
+    pub reference: String,
+    pub time: DateTime<Local>,
+    // `en`, `he` or `bi`, like the shell's `lang`
+    pub language: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+pub fn record_visit(
+    xdg_dirs: &xdg::BaseDirectories,
+    passage: &Passage,
+) -> Result<(), anyhow::Error> {
+    append_visit(
+        xdg_dirs,
+        &Visit {
+            reference: passage.reference.clone(),
+            time: Local::now(),
+            language: passage.language.code().to_string(),
+            version: passage.version.clone(),
+        },
+    )
+}
+
+/// Adds `visit` to the end of the history, for passages that never become a `Passage`.
+pub fn append_visit(xdg_dirs: &xdg::BaseDirectories, visit: &Visit) -> Result<(), anyhow::Error> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(xdg_dirs.place_state_file(HISTORY_FILE)?)?;
+    writeln!(file, "{}", serde_json::to_string(visit)?)?;
+    Ok(())
+}
+
+/// Every visit from oldest to newest, leaving out any line that can't be read.
+pub fn load_history(xdg_dirs: &xdg::BaseDirectories) -> Result<Vec<Visit>, anyhow::Error> {
+    let Some(path) = xdg_dirs.find_state_file(HISTORY_FILE) else {
+        return Ok(vec![]);
+    };
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Every ref in the history once, most recently seen first.
+pub fn recent_refs(history: &[Visit]) -> Vec<String> {
+    let mut refs: Vec<String> = vec![];
+    for visit in history.iter().rev() {
+        if !refs.contains(&visit.reference) {
+            refs.push(visit.reference.clone());
+        }
+    }
+    refs
+}
+
+/// When `--since` starts from, either some time before `now` like `3h`, `7d` or `2w`, or the
+/// start of a day as `YYYY-MM-DD` or a Hebrew date.
+pub fn parse_since(since: &str, now: DateTime<Local>) -> Result<DateTime<Local>, anyhow::Error> {
+    let since = since.trim();
+    let ago = since
+        .find(|c: char| !c.is_ascii_digit())
+        .filter(|idx| *idx > 0)
+        .and_then(|idx| {
+            let count: i64 = since[..idx].parse().ok()?;
+            match &since[idx..] {
+                "h" => TimeDelta::try_hours(count),
+                "d" => TimeDelta::try_days(count),
+                "w" => TimeDelta::try_weeks(count),
+                _ => None,
+            }
+        });
+    let start = match ago {
+        // `None` when it goes back further than any date there can be
+        Some(ago) => now.checked_sub_signed(ago),
+        None => parse_date(since).ok().and_then(|date| {
+            date.and_time(NaiveTime::MIN)
+                .and_local_timezone(Local)
+                .earliest()
+        }),
+    };
+    start.ok_or(anyhow!(
+        "Expected something like `3h`, `7d`, `2w` or a date, not '{}'",
+        since
+    ))
+}
+
+/// The newest `count` visits as a numbered table, oldest first.
+pub fn format_history(history: &[Visit], count: usize) -> String {
+    if history.is_empty() {
+        return "Nothing has been viewed yet".to_string();
+    }
+    let skipped = history.len().saturating_sub(count);
+    let mut table = vec![
+        "|-:|:-|:-|:-|:-|".to_string(),
+        "|**#**|**When**|**Ref**|**Language**|**Version**|".to_string(),
+        "|-:|:-|:-|:-|:-|".to_string(),
+    ];
+    let today = Local::now().date_naive();
+    for (idx, visit) in history.iter().enumerate().skip(skipped) {
+        let when = match visit.time.date_naive() {
+            date if date == today => visit.time.format("Today %H:%M").to_string(),
+            date if Some(date) == today.checked_sub_days(Days::new(1)) => {
+                visit.time.format("Yesterday %H:%M").to_string()
+            }
+            _ => visit.time.format("%Y-%m-%d %H:%M").to_string(),
+        };
+        table.push(format!(
+            "|{}|{}|{}|{}|{}|",
+            idx + 1,
+            when,
+            visit.reference,
+            visit.language,
+            visit.version
+        ));
+    }
+    table.push("|-".to_string());
+    table.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn since() {
+        let now = Local.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        assert_eq!(
+            parse_since("3h", now).unwrap(),
+            Local.with_ymd_and_hms(2024, 5, 10, 9, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_since("2w", now).unwrap(),
+            Local.with_ymd_and_hms(2024, 4, 26, 12, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_since("2024-05-01", now).unwrap(),
+            Local.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap()
+        );
+        assert!(parse_since("3y", now).is_err());
+        assert!(parse_since("100000000d", now).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::anyhow;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::process::Command;
+
+use crate::parser::bible_verse::{parse_verse, BibleRange, Point, Section};
+use crate::parser::toc::{books, Toc};
+
+const MARKS_FILE: &str = "marks.json";
+
+/// A bookmarked ref, along with a note and tags when it was given any.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mark {
+    pub reference: String,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    pub added: NaiveDate,
+}
+
+pub fn load_marks(xdg_dirs: &xdg::BaseDirectories) -> Result<Vec<Mark>, anyhow::Error> {
+    match xdg_dirs.find_data_file(MARKS_FILE) {
+        Some(path) => Ok(serde_json::from_str(&fs::read_to_string(path)?)?),
+        None => Ok(vec![]),
+    }
+}
+
+pub fn save_marks(xdg_dirs: &xdg::BaseDirectories, marks: &[Mark]) -> Result<(), anyhow::Error> {
+    fs::write(
+        xdg_dirs.place_data_file(MARKS_FILE)?,
+        serde_json::to_string_pretty(marks)?,
+    )?;
+    Ok(())
+}
+
+/// Writes a ref the way Sefaria would, with the title from the table of contents, so that
+/// `gen 1.1` and `Genesis 1:1` are marked as the same verse.
+pub fn canonical_ref(toc: &Toc, reference: &str) -> Result<String, anyhow::Error> {
+    let parsed = parse_verse(reference)?;
+    let name = parsed.book.trim().trim_end_matches('.');
+    let title = books(toc)
+        .into_iter()
+        .find(|book| book.title.eq_ignore_ascii_case(name))
+        .map(|book| book.title.clone())
+        .ok_or(anyhow!("'{}' is not a book in the table of contents", name))?;
+    let section = match &parsed.section {
+        Some(section) => Some(section.parse::<Section>()?),
+        None => None,
+    };
+    let place = match (section, &parsed.verse) {
+        (_, Some(BibleRange::ChapterRange((first, verse), (last, last_verse)))) => {
+            format!("{}:{}-{}:{}", first, verse, last, last_verse)
+        }
+        (_, Some(BibleRange::DafRange((first, verse), (last, last_verse)))) => {
+            format!("{}:{}-{}:{}", first, verse, last, last_verse)
+        }
+        (_, Some(BibleRange::SectionRange(first, last))) => format!("{}-{}", first, last),
+        (Some(section), Some(BibleRange::Number(verse))) => format!("{}:{}", section, verse),
+        (Some(section), Some(BibleRange::Range((first, last)))) => {
+            format!("{}:{}-{}", section, first, last)
+        }
+        (Some(section), None) => section.to_string(),
+        _ => {
+            return Err(anyhow!(
+                "Mark a chapter or verse of {}, not the whole book",
+                title
+            ))
+        }
+    };
+    Ok(format!("{} {}", title, place))
+}
+
+/// The notes on `book` with the first and last verse each of them covers.
+pub fn book_notes<'a>(marks: &'a [Mark], book: &str) -> Vec<(Point, Point, &'a str)> {
+    marks
+        .iter()
+        .filter(|mark| !mark.note.is_empty())
+        .filter_map(|mark| {
+            let parsed = parse_verse(&mark.reference).ok()?;
+            let (first, last) = parsed.bounds().ok()??;
+            parsed
+                .book
+                .eq_ignore_ascii_case(book)
+                .then_some((first, last, mark.note.as_str()))
+        })
+        .collect()
+}
+
+/// Opens `note` in `$EDITOR` and hands back what it was changed to.
+pub fn edit_note(note: &str) -> Result<String, anyhow::Error> {
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let words = shlex::split(&editor)
+        .filter(|words| !words.is_empty())
+        .ok_or(anyhow!("Could not make sense of $EDITOR: '{}'", editor))?;
+    let path = std::env::temp_dir().join(format!("rsbbi-note-{}.md", std::process::id()));
+    fs::write(&path, note)?;
+
+    let status = Command::new(&words[0])
+        .args(&words[1..])
+        .arg(&path)
+        .status()
+        .map_err(|nar| anyhow!("Could not open {}: {}", words[0], nar));
+    let edited = fs::read_to_string(&path);
+    fs::remove_file(&path)?;
+    if !status?.success() {
+        return Err(anyhow!(
+            "{} failed, so the note was left as it was",
+            words[0]
+        ));
+    }
+    Ok(edited?.trim().to_string())
+}
+
+/// Every mark as a numbered table, or only those tagged `tag`.
+pub fn format_marks(marks: &[Mark], tag: Option<&str>) -> String {
+    let marks: Vec<_> = marks
+        .iter()
+        .filter(|mark| tag.is_none_or(|tag| mark.tags.contains(tag)))
+        .collect();
+    if marks.is_empty() {
+        return match tag {
+            Some(tag) => format!("Nothing is tagged '{}'", tag),
+            None => "Nothing is marked yet, mark a ref with `rsbbi mark`".to_string(),
+        };
+    }
+    let mut table = vec![
+        "|-:|:-|:-|:-|:-|".to_string(),
+        "|**#**|**Ref**|**Tags**|**Note**|**Added**|".to_string(),
+        "|-:|:-|:-|:-|:-|".to_string(),
+    ];
+    for (idx, mark) in marks.iter().enumerate() {
+        table.push(format!(
+            "|{}|{}|{}|{}|{}|",
+            idx + 1,
+            mark.reference,
+            mark.tags.iter().cloned().collect::<Vec<_>>().join(", "),
+            mark.note.lines().next().unwrap_or_default(),
+            mark.added
+        ));
+    }
+    table.push("|-".to_string());
+    table.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::fixtures;
+
+    #[test]
+    fn canonical_refs() {
+        let toc = fixtures::toc();
+        assert_eq!(canonical_ref(&toc, "genesis 1.1").unwrap(), "Genesis 1:1");
+        assert_eq!(
+            canonical_ref(&toc, "Genesis 1:1-2:3").unwrap(),
+            "Genesis 1:1-2:3"
+        );
+        assert_eq!(
+            canonical_ref(&toc, "berakhot 2a-3b").unwrap(),
+            "Berakhot 2a-3b"
+        );
+        assert!(canonical_ref(&toc, "Genesis").is_err());
+        assert!(canonical_ref(&toc, "Exodus 1").is_err());
+    }
+}
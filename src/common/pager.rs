@@ -0,0 +1,38 @@
+use std::env;
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use termimad::crossterm::terminal;
+use termimad::MadSkin;
+
+/// Prints markdown through the skin, but hands it to `$PAGER` (or `less -R`) when it would not fit
+/// on the terminal, so whole books don't vanish into the scrollback.
+pub fn print_paged(skin: &MadSkin, text: &str) {
+    let rendered = skin.term_text(text).to_string();
+    let height = terminal::size()
+        .map(|(_, rows)| rows as usize)
+        .unwrap_or(usize::MAX);
+
+    if !std::io::stdout().is_terminal() || rendered.lines().count() < height {
+        print!("{}", rendered);
+        return;
+    }
+
+    let pager = env::var("PAGER").unwrap_or("less -R".to_string());
+    let mut pager_parts = pager.split_whitespace();
+    let spawned = Command::new(pager_parts.next().unwrap_or("less"))
+        .args(pager_parts)
+        .stdin(Stdio::piped())
+        .spawn();
+
+    match spawned {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The user quitting the pager early closes the pipe, which is fine
+                let _ = stdin.write_all(rendered.as_bytes());
+            }
+            let _ = child.wait();
+        }
+        Err(_) => print!("{}", rendered),
+    }
+}
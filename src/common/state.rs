@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::parser::search::Passage;
+
+/// The last passage that was shown, so that `next` and `prev` can continue from it.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastViewed {
+    pub reference: String,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl LastViewed {
+    pub fn load(path: &Path) -> Option<LastViewed> {
+        let json = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl From<&Passage> for LastViewed {
+    fn from(passage: &Passage) -> Self {
+        LastViewed {
+            reference: passage.reference.clone(),
+            next: passage.next.clone(),
+            prev: passage.prev.clone(),
+        }
+    }
+}
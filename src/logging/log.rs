@@ -14,17 +14,22 @@ pub struct Log<'a> {
 
 impl Log<'_> {
     #[cfg(feature = "tetragrammaton-logging")]
-    pub fn new(path: &Path) -> Result<Log, std::io::Error> {
+    pub fn new(path: &Path) -> Result<Log<'_>, std::io::Error> {
         let prefix = &path.parent().unwrap();
         std::fs::create_dir_all(prefix).unwrap();
-        match OpenOptions::new().create(true).write(true).open(path) {
+        match OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(path)
+        {
             Ok(_) => Ok(Log { file: path }),
             Err(e) => Err(e),
         }
     }
 
     #[cfg(not(feature = "tetragrammaton-logging"))]
-    pub fn new(path: &Path) -> Result<Log, std::io::Error> {
+    pub fn new(path: &Path) -> Result<Log<'_>, std::io::Error> {
         Ok(Log { file: path })
     }
 
@@ -1,7 +1,18 @@
+pub mod alt;
 pub mod args;
 pub mod bible_verse;
+pub mod daf;
+pub mod grep;
+pub mod hebrew;
+pub mod identify;
+pub mod index;
 pub mod info;
 pub mod keyword;
+pub mod library;
+pub mod metadata;
+pub mod scan;
+pub mod search;
 pub mod shape;
 pub mod tetragrammaton;
 pub mod text;
+pub mod toc;
@@ -0,0 +1,227 @@
+use anyhow::anyhow;
+
+use super::bible_verse::parse_verse;
+use super::metadata::{cached_index_record, AltNode, IndexRecord};
+use super::toc::{books, default_toc, find_category};
+
+// Ways of writing "the parasha of" before its name
+static PARASHA_PREFIXES: [&str; 5] = ["parashat ", "parshat ", "parashas ", "parasha ", "parsha "];
+
+/// Names without case, spaces or punctuation, so `Lech-Lecha` is `Lech Lecha`.
+fn simplify(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The part of a book's alternate structures called `name`, only looking in `structure` if given.
+fn find_node<'a>(
+    record: &'a IndexRecord,
+    structure: Option<&str>,
+    name: &str,
+) -> Option<&'a AltNode> {
+    let name = simplify(name);
+    record
+        .alt_structs
+        .iter()
+        .filter(|(alt_name, _)| structure.is_none_or(|structure| *alt_name == structure))
+        .flat_map(|(_, alt)| &alt.nodes)
+        .find(|node| {
+            simplify(node.name()) == name
+                || node
+                    .titles
+                    .iter()
+                    .any(|title| simplify(&title.text) == name)
+        })
+}
+
+/// Looks for a parasha in every book of the Torah.
+fn find_parasha(
+    xdg_dirs: &xdg::BaseDirectories,
+    name: &str,
+) -> Result<Option<AltNode>, anyhow::Error> {
+    let toc = default_toc()?;
+    let Some((_, torah)) = find_category(&toc, "Tanakh/Torah") else {
+        return Ok(None);
+    };
+    for book in books(&torah.contents) {
+        let record = cached_index_record(xdg_dirs, &book.title)?;
+        if let Some(node) = find_node(&record, Some("Parasha"), name) {
+            return Ok(Some(node.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// `Exodus 35:1-38:20` and `Exodus 38:21-40:38` to `Exodus 35:1-40:38`.
+fn join_refs(first: &str, last: &str) -> String {
+    let start = first.split_once('-').map_or(first, |(start, _)| start);
+    match last.rsplit_once('-') {
+        Some((_, end)) => format!("{}-{}", start, end),
+        None => format!(
+            "{}-{}",
+            start,
+            last.rsplit_once(' ').map_or(last, |(_, end)| end)
+        ),
+    }
+}
+
+/// The ref a parasha covers, including combined ones like `Vayakhel-Pekudei`.
+fn parasha_ref(
+    xdg_dirs: &xdg::BaseDirectories,
+    name: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    if let Some(node) = find_parasha(xdg_dirs, name)? {
+        return Ok(Some(node.whole_ref));
+    }
+    if let Some((first, second)) = name.split_once('-') {
+        if let (Some(first), Some(second)) = (
+            find_parasha(xdg_dirs, first)?,
+            find_parasha(xdg_dirs, second)?,
+        ) {
+            return Ok(Some(join_refs(&first.whole_ref, &second.whole_ref)));
+        }
+    }
+    Ok(None)
+}
+
+/// Turns a named part of a book into the ref it covers: `Parashat Noach`, `Bereshit, aliyah 3`,
+/// or a book and one of its parts, like `Berakhot, Me'ematai`. `None` means it isn't written like
+/// any of these, so it's probably a usual ref. The index records of the Torah are only downloaded
+/// the first time.
+pub fn resolve_alt(
+    xdg_dirs: &xdg::BaseDirectories,
+    input: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    let input = input.trim();
+    // ASCII only, so offsets in it are the same as in `input`
+    let lowered = input.to_ascii_lowercase();
+
+    let (head, aliyah) = match lowered.rfind(", aliyah ") {
+        Some(idx) => {
+            let aliyah = input[idx + ", aliyah ".len()..].trim();
+            (
+                &input[..idx],
+                Some(
+                    aliyah
+                        .parse::<usize>()
+                        .map_err(|_| anyhow!("'{}' is not an aliyah", aliyah))?,
+                ),
+            )
+        }
+        None => (input, None),
+    };
+    let prefix = PARASHA_PREFIXES
+        .iter()
+        .find(|prefix| lowered.starts_with(*prefix));
+    let name = prefix.map_or(head, |prefix| &head[prefix.len()..]);
+
+    if let Some(aliyah) = aliyah {
+        let node = find_parasha(xdg_dirs, name)?
+            .ok_or(anyhow!("There is no parasha called '{}'", name))?;
+        return node
+            .refs
+            .get(aliyah.wrapping_sub(1))
+            .cloned()
+            .map(Some)
+            .ok_or(anyhow!(
+                "{} only has {} aliyot",
+                node.name(),
+                node.refs.len()
+            ));
+    }
+    if prefix.is_some() {
+        return parasha_ref(xdg_dirs, name)?
+            .map(Some)
+            .ok_or(anyhow!("There is no parasha called '{}'", name));
+    }
+
+    // Complex books like `Tur, Orach Chaim 1` are refs already, and only Sefaria knows them
+    if parse_verse(input).is_ok() {
+        return Ok(None);
+    }
+    if let Some((book, part)) = input.split_once(", ") {
+        let toc = default_toc()?;
+        let title = books(&toc)
+            .into_iter()
+            .find(|found| found.title.eq_ignore_ascii_case(book.trim()))
+            .map(|found| found.title.clone());
+        if let Some(title) = title {
+            let record = cached_index_record(xdg_dirs, &title)?;
+            return Ok(find_node(&record, None, part).map(|node| node.whole_ref.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// Every part of one of a book's alternate structures along with what it covers.
+pub fn format_alt(record: &IndexRecord, structure: &str) -> Result<String, anyhow::Error> {
+    let (name, alt) = record
+        .alt_structs
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(structure))
+        .ok_or(anyhow!(
+            "{} has no structure called '{}', only: {}",
+            record.title,
+            structure,
+            if record.alt_structs.is_empty() {
+                "chapters".to_string()
+            } else {
+                record
+                    .alt_structs
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        ))?;
+
+    let mut table = vec![
+        format!("# {} ~ {}", record.title, name),
+        "|-:|:-|:-|".to_string(),
+        format!("|**#**|**{}**|**Covers**|", name),
+        "|-:|:-|:-|".to_string(),
+    ];
+    for (idx, node) in alt.nodes.iter().enumerate() {
+        let covers = node
+            .whole_ref
+            .strip_prefix(&record.title)
+            .unwrap_or(&node.whole_ref)
+            .trim();
+        table.push(format!("|{}|{}|{}|", idx + 1, node.name(), covers));
+    }
+    table.push("|-".to_string());
+    Ok(table.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_parts() {
+        let record: IndexRecord = serde_json::from_str(
+            r#"{"title": "Genesis", "alt_structs": {"Parasha": {"nodes": [
+                {"sharedTitle": "Lech Lecha", "wholeRef": "Genesis 12:1-17:27", "refs": []}
+            ]}}}"#,
+        )
+        .unwrap();
+        assert!(find_node(&record, Some("Parasha"), "lech-lecha").is_some());
+        assert!(find_node(&record, Some("Chapters"), "Lech Lecha").is_none());
+        assert!(format_alt(&record, "parasha")
+            .unwrap()
+            .contains("|1|Lech Lecha|12:1-17:27|"));
+        assert_eq!(
+            join_refs("Exodus 35:1-38:20", "Exodus 38:21-40:38"),
+            "Exodus 35:1-40:38"
+        );
+    }
+
+    #[test]
+    fn leaves_refs_alone() {
+        let xdg_dirs = xdg::BaseDirectories::new().unwrap();
+        assert_eq!(resolve_alt(&xdg_dirs, "Tur, Orach Chaim 1").unwrap(), None);
+        assert_eq!(resolve_alt(&xdg_dirs, "Genesis 1:1").unwrap(), None);
+    }
+}
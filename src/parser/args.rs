@@ -1,6 +1,10 @@
 use clap::{Parser, Subcommand};
 use clap_num::number_range;
 
+use super::keyword::{Mode, Sort};
+use super::scan::ScanOutput;
+use crate::calendar::learning::Cycle;
+
 /// RSBBI is a rust based Judaism text viewer
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None, arg_required_else_help = true)]
@@ -24,6 +28,90 @@ pub enum Commands {
         #[clap(long, default_value_t = false)]
         hebrew: bool,
 
+        /// Limit a whole book to chapters like `3-5` or dafim like `2a-5b`, needed past 50 of them
+        #[clap(short, long)]
+        chapters: Option<String>,
+
+        /// Show the passage after this one instead
+        #[clap(long, conflicts_with = "prev")]
+        next: bool,
+
+        /// Show the passage before this one instead
+        #[clap(long)]
+        prev: bool,
+
+        /// Verse
+        #[clap(required = true)]
+        rest: Vec<String>,
+    },
+
+    /// Show the next passage
+    ///
+    /// Show the chapter or amud after the last one that was viewed
+    #[clap(alias = "n")]
+    Next {
+        /// Include line numbers
+        #[clap(short, long)]
+        lines: bool,
+
+        /// Use Hebrew instead of English
+        #[clap(long, default_value_t = false)]
+        hebrew: bool,
+    },
+
+    /// Show the previous passage
+    ///
+    /// Show the chapter or amud before the last one that was viewed
+    #[clap(alias = "p")]
+    Prev {
+        /// Include line numbers
+        #[clap(short, long)]
+        lines: bool,
+
+        /// Use Hebrew instead of English
+        #[clap(long, default_value_t = false)]
+        hebrew: bool,
+    },
+
+    /// List passages viewed before
+    History {
+        /// Only list passages viewed since then, like `3h`, `7d`, `2w` or a date
+        #[clap(short, long)]
+        since: Option<String>,
+
+        /// How many of the newest to list
+        #[clap(short = 'n', long, default_value_t = 20)]
+        count: usize,
+    },
+
+    /// Open the last passage viewed again
+    Resume {
+        /// Include line numbers
+        #[clap(short, long)]
+        lines: bool,
+
+        /// Use Hebrew instead of the language it was viewed in
+        #[clap(long, default_value_t = false)]
+        hebrew: bool,
+    },
+
+    /// Read a text interactively
+    ///
+    /// Open a full screen, scrollable reader that can move through chapters
+    #[clap(alias = "r")]
+    Read {
+        /// Include line numbers
+        #[clap(short, long)]
+        lines: bool,
+
+        /// Use Hebrew instead of English
+        #[clap(long, default_value_t = false)]
+        hebrew: bool,
+
+        /// Show Hebrew and English together
+        #[clap(short, long, conflicts_with = "hebrew")]
+        bilingual: bool,
+
         /// Verse
         #[clap(required = true)]
         rest: Vec<String>,
@@ -38,6 +126,74 @@ pub enum Commands {
         #[clap(short, long, default_value_t = 50, value_parser=more_than_zero)]
         size: i32,
 
+        /// Page of results to show, each page being `size` hits long
+        #[clap(short, long, conflicts_with = "from", value_parser = clap::value_parser!(i64).range(1..))]
+        page: Option<i64>,
+
+        /// Hit to start showing results from
+        #[clap(short, long, value_parser = clap::value_parser!(i64).range(0..))]
+        from: Option<i64>,
+
+        /// Keep going page by page until every hit is shown
+        #[clap(short, long)]
+        all: bool,
+
+        /// Only search in a category or book, like `Talmud` or `Mishneh Torah`
+        #[clap(long = "in", value_name = "CATEGORY_OR_BOOK")]
+        within: Vec<String>,
+
+        /// Leave out hits from a category
+        #[clap(short = 'x', long, value_name = "CATEGORY")]
+        exclude: Vec<String>,
+
+        /// Count hits by category and book instead of showing them
+        #[clap(long)]
+        facets: bool,
+
+        /// Draw the counts from `--facets` as bars
+        #[clap(long, requires = "facets")]
+        chart: bool,
+
+        /// How much of the path `--facets` counts categories by, 2 being like `Talmud/Bavli`
+        #[clap(long, requires = "facets", default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+        depth: u32,
+
+        /// How closely hits have to match
+        #[clap(short, long, value_enum, default_value_t = Mode::Exact)]
+        mode: Mode,
+
+        /// How many words can come between the words searched for
+        #[clap(long, value_parser = clap::value_parser!(i32).range(0..))]
+        slop: Option<i32>,
+
+        /// Show the full text of every hit instead of fragments
+        #[clap(short, long)]
+        expand: bool,
+
+        /// Verses to show around every hit with `--expand`
+        #[clap(short, long, default_value_t = 0, requires = "expand")]
+        context: usize,
+
+        /// Include line numbers in hits shown with `--expand`
+        #[clap(long, requires = "expand")]
+        lines: bool,
+
+        /// What order to show hits in
+        #[clap(long, value_enum, default_value_t = Sort::Relevance)]
+        sort: Sort,
+
+        /// Show how well every hit matched
+        #[clap(long)]
+        show_score: bool,
+
+        /// Show every version of a passage that matched, not just the first
+        #[clap(long)]
+        versions: bool,
+
+        /// Search the books cached with `index add` instead of Sefaria
+        #[clap(short, long, conflicts_with = "expand")]
+        local: bool,
+
         /// Verse
         #[clap(required = true)]
         rest: Vec<String>,
@@ -48,10 +204,269 @@ pub enum Commands {
     /// Get info on a book in the Sefaria library
     #[clap(aliases = &["i", "in", "inf"])]
     Info {
+        /// List the parts of another way of dividing the book, like `parasha`
+        #[clap(short, long)]
+        alt: Option<String>,
         /// Book
         #[clap(required = true)]
         book: Vec<String>,
     },
+
+    /// Convert a date between the Gregorian and Hebrew calendars
+    ///
+    /// Takes `YYYY-MM-DD` or a Hebrew date like `25 Kislev 5785` or `כ״ה כסלו תשפ״ה`, and
+    /// shows today when given nothing
+    Date {
+        /// Date
+        date: Vec<String>,
+    },
+
+    /// Show the parasha of the coming Shabbat
+    ///
+    /// Worked out from the calendar, including parashiyot read together and holidays that take
+    /// the place of the parasha
+    Parasha {
+        /// Any day of the week before the Shabbat, as `YYYY-MM-DD` or a Hebrew date
+        #[clap(short, long)]
+        date: Option<String>,
+
+        /// Use the reading in Israel
+        #[clap(long, conflicts_with = "diaspora")]
+        israel: bool,
+
+        /// Use the reading outside of Israel, which is the default
+        #[clap(long)]
+        diaspora: bool,
+
+        /// Show the text of the parasha and its haftarah
+        #[clap(short, long)]
+        show: bool,
+
+        /// Include line numbers
+        #[clap(short, long)]
+        lines: bool,
+
+        /// Use Hebrew instead of English
+        #[clap(long, default_value_t = false)]
+        hebrew: bool,
+    },
+
+    /// Show what the daily learning cycles learn today
+    ///
+    /// Daf Yomi, Yerushalmi Yomi, Mishnah Yomit, Rambam, 929 and Tehillim, worked out offline once
+    /// the shapes of the Mishnah and Tanakh have been downloaded. Asks which to show unless `--show`
+    /// is passed, and Yerushalmi Yomi is only listed since Sefaria has no Vilna dafim
+    Today {
+        /// Any other day, as `YYYY-MM-DD` or a Hebrew date
+        #[clap(short, long)]
+        date: Option<String>,
+
+        /// Show the learning of a cycle
+        #[clap(short, long, value_enum)]
+        show: Option<Cycle>,
+
+        /// Include line numbers
+        #[clap(short, long)]
+        lines: bool,
+
+        /// Use Hebrew instead of English
+        #[clap(long, default_value_t = false)]
+        hebrew: bool,
+    },
+
+    /// Follow study plans through a book or category
+    Plan {
+        #[command(subcommand)]
+        action: PlanAction,
+    },
+
+    /// Bookmark a ref
+    ///
+    /// Marking a ref again adds to its tags, and replaces its note when `--note` is passed
+    Mark {
+        /// Note to keep with it, shown under its verses when searching
+        #[clap(short, long)]
+        note: Option<String>,
+
+        /// Tag to file it under, can be passed more than once
+        #[clap(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Remove the bookmark, along with its note and tags
+        #[clap(short, long, conflicts_with_all = ["note", "tags"])]
+        remove: bool,
+
+        /// Verse
+        #[clap(required = true)]
+        rest: Vec<String>,
+    },
+
+    /// List bookmarks
+    Marks {
+        /// Only list bookmarks with this tag
+        #[clap(short, long)]
+        tag: Option<String>,
+    },
+
+    /// Write notes on refs
+    Note {
+        #[command(subcommand)]
+        action: NoteAction,
+    },
+
+    /// Find where a quotation is from
+    ///
+    /// Reads the quotation from stdin when none is given
+    #[clap(alias = "id")]
+    Identify {
+        /// How many possible sources to show
+        #[clap(short, long, default_value_t = 5)]
+        candidates: usize,
+
+        /// Quotation, in Hebrew or English
+        rest: Vec<String>,
+    },
+
+    /// Find the references in a document
+    ///
+    /// Works on Markdown or plain text, and only finds books that are in the table of contents
+    Scan {
+        /// What to print
+        #[clap(short, long, value_enum, default_value_t = ScanOutput::List)]
+        output: ScanOutput,
+
+        /// Quote Hebrew instead of English with `--output inline`
+        #[clap(long, default_value_t = false)]
+        hebrew: bool,
+
+        /// Document to scan
+        file: std::path::PathBuf,
+    },
+
+    /// Search cached books with a regular expression
+    ///
+    /// Goes through every book downloaded with `index add`, or only those given with `--in`
+    Grep {
+        /// Only search in a category or book, like `Tanakh` or `Genesis`
+        #[clap(long = "in", value_name = "CATEGORY_OR_BOOK")]
+        within: Vec<String>,
+
+        /// Match upper and lower case alike
+        #[clap(short, long)]
+        ignore_case: bool,
+
+        /// Match Hebrew with or without nikkud and ta'amim
+        #[clap(short = 'n', long)]
+        ignore_nikkud: bool,
+
+        /// Match any word made from a three letter Hebrew root, like `שמר`
+        #[clap(short, long)]
+        root: bool,
+
+        /// Regular expression, or a root with `--root`
+        pattern: String,
+    },
+
+    /// Manage books cached for offline search
+    Index {
+        #[command(subcommand)]
+        action: IndexAction,
+    },
+
+    /// Run commands interactively
+    ///
+    /// Start a shell that keeps its settings, history and downloads between commands
+    #[clap(alias = "sh")]
+    Shell,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IndexAction {
+    /// Download whole books and add them to the index
+    Add {
+        /// Books
+        #[clap(required = true)]
+        books: Vec<String>,
+    },
+
+    /// Index every cached book again
+    Rebuild,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PlanAction {
+    /// Split a book or category into daily portions of whole chapters
+    ///
+    /// Like `rsbbi plan create Mishnah Berakhot --days 30` or `rsbbi plan create Tanakh`
+    Create {
+        /// Finish in this many days
+        #[clap(short, long, conflicts_with = "per_day", value_parser = clap::value_parser!(u64).range(1..))]
+        days: Option<u64>,
+
+        /// Chapters to learn every day
+        #[clap(short, long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        per_day: u64,
+
+        /// What to call the plan, the book or category by default
+        #[clap(short, long)]
+        name: Option<String>,
+
+        /// The first day, as `YYYY-MM-DD` or a Hebrew date, today by default
+        #[clap(short, long)]
+        start: Option<String>,
+
+        /// Book or category
+        #[clap(required = true)]
+        target: Vec<String>,
+    },
+
+    /// List every plan and how it's going
+    List,
+
+    /// Show the first portion of a plan that hasn't been learned yet
+    Next {
+        /// Include line numbers
+        #[clap(short, long)]
+        lines: bool,
+
+        /// Use Hebrew instead of English
+        #[clap(long, default_value_t = false)]
+        hebrew: bool,
+
+        /// Plan, which can be left out when there is only one
+        plan: Vec<String>,
+    },
+
+    /// Mark the next portion of a plan as learned
+    Mark {
+        /// Mark this day of the plan instead
+        #[clap(short, long, value_parser = clap::value_parser!(u64).range(1..))]
+        day: Option<u64>,
+
+        /// Mark it as not learned
+        #[clap(short, long)]
+        undo: bool,
+
+        /// Plan, which can be left out when there is only one
+        plan: Vec<String>,
+    },
+
+    /// Delete a plan
+    Remove {
+        /// Plan
+        #[clap(required = true)]
+        plan: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NoteAction {
+    /// Edit the note on a ref in `$EDITOR`, bookmarking the ref if it isn't already
+    Edit {
+        /// Verse
+        #[clap(required = true)]
+        rest: Vec<String>,
+    },
 }
 
 fn more_than_zero(num: &str) -> Result<i32, String> {
@@ -1,27 +1,93 @@
 use anyhow::anyhow;
+use pest::iterators::Pairs;
 use pest::Parser;
 use pest_derive::Parser;
+use std::fmt;
+use std::str::FromStr;
+
+use super::daf::Daf;
 
 #[derive(Parser)]
 #[grammar = "./parser/bible.pest"]
 pub struct BibleVerse;
 
-pub fn range_to_rs_range(range: &str) -> (usize, usize) {
+pub fn range_to_rs_range(range: &str) -> Result<(usize, usize), anyhow::Error> {
     let mut parts = range.split('-');
-    (
-        parts
-            .next()
-            .unwrap()
-            .trim()
-            .parse()
-            .expect("Could not parse first number in range"),
-        parts
-            .next()
-            .unwrap()
-            .trim()
-            .parse()
-            .expect("Could not parse second number in range"),
-    )
+    let mut next_number = || -> Result<usize, anyhow::Error> {
+        let part = parts.next().unwrap_or_default().trim();
+        part.parse()
+            .map_err(|_| anyhow!("Could not parse '{}' in range '{}' as a verse", part, range))
+    };
+    Ok((next_number()?, next_number()?))
+}
+
+/// Parses either a single section (`3`) or a range of them (`3-5`, `2a-5b`).
+pub fn parse_section_range(range: &str) -> Result<(Section, Section), anyhow::Error> {
+    let (first, last) = match range.split_once('-') {
+        Some((first, last)) => (first.trim().parse()?, last.trim().parse()?),
+        None => {
+            let section: Section = range.trim().parse()?;
+            (section, section)
+        }
+    };
+    if std::mem::discriminant(&first) != std::mem::discriminant(&last) {
+        return Err(anyhow!(
+            "Cannot mix chapters and dafs in a range: {}",
+            range
+        ));
+    }
+    Ok((first, last))
+}
+
+/// A section of a book, either a plain chapter or a Talmud daf/amud.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    // Genesis ^1^
+    Chapter(usize),
+    // Berakhot ^2a^
+    Daf(Daf),
+}
+
+impl Section {
+    /// The 1-based index Sefaria uses for this section in its text and shape arrays.
+    pub fn index(&self) -> usize {
+        match self {
+            Section::Chapter(num) => *num,
+            Section::Daf(daf) => daf.index(),
+        }
+    }
+
+    /// The section `offset` places after this one, keeping the same kind of addressing.
+    pub fn offset(&self, offset: usize) -> Section {
+        match self {
+            Section::Chapter(num) => Section::Chapter(num + offset),
+            Section::Daf(daf) => Section::Daf(Daf::from_index(daf.index() + offset).unwrap()),
+        }
+    }
+}
+
+impl FromStr for Section {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('0') {
+            return Err(anyhow!("Section starts with '0' which is forbidden: {}", s));
+        }
+        if s.chars().all(|c| c.is_ascii_digit()) {
+            Ok(Section::Chapter(s.parse()?))
+        } else {
+            Ok(Section::Daf(s.parse()?))
+        }
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Section::Chapter(num) => write!(f, "{}", num),
+            Section::Daf(daf) => write!(f, "{}", daf),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -34,8 +100,15 @@ pub enum BibleRange {
     //ReturnedBibleVerse.section so that ChapterRange((first_section, first_verse),
     //(second_section, second_verse))
     ChapterRange((usize, usize), (usize, usize)),
+    // Same as ChapterRange, but for ^2a:3-5b:4^
+    DafRange((Daf, usize), (Daf, usize)),
+    // Whole sections, so ^1-3^ or ^2a-5b^. Also overrides ReturnedBibleVerse.section
+    SectionRange(Section, Section),
 }
 
+/// A (section index, verse) position in a book.
+pub type Point = (usize, usize);
+
 #[derive(Debug, PartialEq)]
 pub struct ReturnedBibleVerse {
     pub book: String,
@@ -43,9 +116,83 @@ pub struct ReturnedBibleVerse {
     pub verse: Option<BibleRange>,
 }
 
+impl ReturnedBibleVerse {
+    /// The section and verse that the first returned line corresponds to, used for labeling
+    /// lines. `None` means the reference had no section at all.
+    pub fn starting_point(&self) -> Result<Option<(Section, usize)>, anyhow::Error> {
+        let section = match &self.section {
+            Some(section) => Some(section.parse::<Section>()?),
+            None => None,
+        };
+        Ok(match (&self.verse, section) {
+            (Some(BibleRange::ChapterRange((first, verse), _)), _) => {
+                Some((Section::Chapter(*first), *verse))
+            }
+            (Some(BibleRange::DafRange((first, verse), _)), _) => {
+                Some((Section::Daf(*first), *verse))
+            }
+            (Some(BibleRange::SectionRange(first, _)), _) => Some((*first, 1)),
+            (Some(BibleRange::Number(num)), Some(section)) => Some((section, *num)),
+            (Some(BibleRange::Range((first, _))), Some(section)) => Some((section, *first)),
+            (None, Some(section)) => Some((section, 1)),
+            _ => None,
+        })
+    }
+
+    /// The first and last (section index, verse) the reference covers, with a whole section
+    /// running to `usize::MAX`. `None` means the reference had no section at all.
+    pub fn bounds(&self) -> Result<Option<(Point, Point)>, anyhow::Error> {
+        let section = match &self.section {
+            Some(section) => Some(section.parse::<Section>()?.index()),
+            None => None,
+        };
+        Ok(match (&self.verse, section) {
+            (Some(BibleRange::ChapterRange(first, last)), _) => Some((*first, *last)),
+            (Some(BibleRange::DafRange((first, verse), (last, last_verse))), _) => {
+                Some(((first.index(), *verse), (last.index(), *last_verse)))
+            }
+            (Some(BibleRange::SectionRange(first, last)), _) => {
+                Some(((first.index(), 1), (last.index(), usize::MAX)))
+            }
+            (Some(BibleRange::Number(num)), Some(section)) => {
+                Some(((section, *num), (section, *num)))
+            }
+            (Some(BibleRange::Range((first, last))), Some(section)) => {
+                Some(((section, *first), (section, *last)))
+            }
+            (None, Some(section)) => Some(((section, 1), (section, usize::MAX))),
+            _ => None,
+        })
+    }
+}
+
+fn tagged_section(pairs: &Pairs<Rule>, tag: &str) -> Result<Section, anyhow::Error> {
+    pairs
+        .clone()
+        .find_first_tagged(tag)
+        .ok_or(anyhow!("Could not find '{}' in reference", tag))?
+        .as_str()
+        .parse()
+}
+
+fn tagged_verse(pairs: &Pairs<Rule>, tag: &str) -> Result<usize, anyhow::Error> {
+    let verse = pairs
+        .clone()
+        .find_first_tagged(tag)
+        .ok_or(anyhow!("Could not find '{}' in reference", tag))?
+        .as_str();
+    if verse.starts_with('0') {
+        return Err(anyhow!(
+            "Verse number starts with '0' which is forbidden: {}",
+            verse
+        ));
+    }
+    Ok(verse.parse()?)
+}
+
 pub fn parse_verse(verse: &str) -> Result<ReturnedBibleVerse, anyhow::Error> {
     let parsed_bible_verse = BibleVerse::parse(Rule::total, verse)
-        .expect("Could not parse bible verse")
+        .map_err(|err| anyhow!("Could not parse bible verse '{}':\n{}", verse, err))?
         .next()
         .unwrap();
 
@@ -58,19 +205,15 @@ pub fn parse_verse(verse: &str) -> Result<ReturnedBibleVerse, anyhow::Error> {
             Rule::EOI => break,
             Rule::book => book = line.as_str().to_string(),
             Rule::section => {
+                // Validates that this is either a chapter or a daf
+                line.as_str().parse::<Section>()?;
                 section = Some(line.as_str().to_string());
-                if section.is_some() && section.clone().unwrap().starts_with('0') {
-                    return Err(anyhow!(
-                        "Section starts with '0' which is forbidden: {}",
-                        section.unwrap()
-                    ));
-                }
             }
             Rule::verse => {
                 opt_bible_verse_range = match line.clone().into_inner().next().unwrap().as_rule() {
                     Rule::range => Some(BibleRange::Range(range_to_rs_range(
                         line.into_inner().next().unwrap().as_str().trim(),
-                    ))),
+                    )?)),
                     Rule::verse_number => {
                         let number: usize = line
                             .into_inner()
@@ -93,44 +236,43 @@ pub fn parse_verse(verse: &str) -> Result<ReturnedBibleVerse, anyhow::Error> {
                 };
             }
             Rule::chapter_range => {
-                //FIX: WTF
-                let first_section = parsed_bible_verse
-                    .clone()
-                    .into_inner()
-                    .find_first_tagged("first_section")
-                    .unwrap()
-                    .as_str()
-                    .parse::<usize>()
-                    .unwrap();
-                let first_verse = parsed_bible_verse
-                    .clone()
-                    .into_inner()
-                    .find_first_tagged("first_verse")
-                    .unwrap()
-                    .as_str()
-                    .parse::<usize>()
-                    .unwrap();
-                let second_section = parsed_bible_verse
-                    .clone()
-                    .into_inner()
-                    .find_first_tagged("second_section")
-                    .unwrap()
-                    .as_str()
-                    .parse::<usize>()
-                    .unwrap();
-                let second_verse = parsed_bible_verse
-                    .clone()
-                    .into_inner()
-                    .find_first_tagged("second_verse")
-                    .unwrap()
-                    .as_str()
-                    .parse::<usize>()
-                    .unwrap();
-
-                opt_bible_verse_range = Some(BibleRange::ChapterRange(
-                    (first_section, first_verse),
-                    (second_section, second_verse),
-                ))
+                let pairs = line.into_inner();
+                let first_section = tagged_section(&pairs, "first_section")?;
+                let first_verse = tagged_verse(&pairs, "first_verse")?;
+                let second_section = tagged_section(&pairs, "second_section")?;
+                let second_verse = tagged_verse(&pairs, "second_verse")?;
+
+                opt_bible_verse_range = Some(match (first_section, second_section) {
+                    (Section::Chapter(first), Section::Chapter(second)) => {
+                        BibleRange::ChapterRange((first, first_verse), (second, second_verse))
+                    }
+                    (Section::Daf(first), Section::Daf(second)) => {
+                        BibleRange::DafRange((first, first_verse), (second, second_verse))
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "Cannot mix chapters and dafs in a range: {}-{}",
+                            first_section,
+                            second_section
+                        ))
+                    }
+                });
+            }
+            Rule::section_range => {
+                let pairs = line.into_inner();
+                let first_section = tagged_section(&pairs, "first_section")?;
+                let second_section = tagged_section(&pairs, "second_section")?;
+
+                if std::mem::discriminant(&first_section) != std::mem::discriminant(&second_section)
+                {
+                    return Err(anyhow!(
+                        "Cannot mix chapters and dafs in a range: {}-{}",
+                        first_section,
+                        second_section
+                    ));
+                }
+                opt_bible_verse_range =
+                    Some(BibleRange::SectionRange(first_section, second_section));
             }
             _ => (),
         }
@@ -147,6 +289,7 @@ pub fn parse_verse(verse: &str) -> Result<ReturnedBibleVerse, anyhow::Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::daf::Amud;
 
     #[test]
     fn simple_name() {
@@ -375,4 +518,100 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn talmud_section_range() {
+        assert_eq!(
+            parse_verse("Berakhot 2a-5b").unwrap(),
+            ReturnedBibleVerse {
+                book: "Berakhot".to_string(),
+                section: None,
+                verse: Some(BibleRange::SectionRange(
+                    Section::Daf(Daf {
+                        daf: 2,
+                        amud: Amud::A
+                    }),
+                    Section::Daf(Daf {
+                        daf: 5,
+                        amud: Amud::B
+                    })
+                )),
+            }
+        );
+    }
+
+    #[test]
+    fn talmud_daf_range() {
+        assert_eq!(
+            parse_verse("Berakhot 2a:3-5b:4").unwrap(),
+            ReturnedBibleVerse {
+                book: "Berakhot".to_string(),
+                section: None,
+                verse: Some(BibleRange::DafRange(
+                    (
+                        Daf {
+                            daf: 2,
+                            amud: Amud::A
+                        },
+                        3
+                    ),
+                    (
+                        Daf {
+                            daf: 5,
+                            amud: Amud::B
+                        },
+                        4
+                    )
+                )),
+            }
+        );
+    }
+
+    #[test]
+    fn talmud_starting_point() {
+        assert_eq!(
+            parse_verse("Shabbat 31a:6")
+                .unwrap()
+                .starting_point()
+                .unwrap(),
+            Some((
+                Section::Daf(Daf {
+                    daf: 31,
+                    amud: Amud::A
+                }),
+                6
+            ))
+        );
+    }
+
+    #[test]
+    fn chapter_section_range() {
+        assert_eq!(
+            parse_verse("Genesis 1-3").unwrap(),
+            ReturnedBibleVerse {
+                book: "Genesis".to_string(),
+                section: None,
+                verse: Some(BibleRange::SectionRange(
+                    Section::Chapter(1),
+                    Section::Chapter(3)
+                )),
+            }
+        );
+    }
+
+    #[test]
+    fn reference_bounds() {
+        let bounds = |reference: &str| parse_verse(reference).unwrap().bounds().unwrap();
+        assert_eq!(bounds("Genesis 1:1-6:8"), Some(((1, 1), (6, 8))));
+        assert_eq!(bounds("Genesis 6:9-12"), Some(((6, 9), (6, 12))));
+        assert_eq!(bounds("Genesis 3"), Some(((3, 1), (3, usize::MAX))));
+        assert_eq!(bounds("Berakhot 2a:1-2b:3"), Some(((3, 1), (4, 3))));
+        assert_eq!(bounds("Genesis"), None);
+    }
+
+    #[test]
+    fn mixed_section_range() {
+        assert!(parse_verse("Berakhot 2-5b").is_err());
+        assert!(parse_verse("Berakhot 2:2a-3b").is_err());
+    }
 }
@@ -0,0 +1,116 @@
+use anyhow::anyhow;
+use std::fmt;
+use std::str::FromStr;
+
+/// One side of a page of Talmud.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Amud {
+    A,
+    B,
+}
+
+/// A daf/amud address such as `2a` or `31b`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Daf {
+    pub daf: usize,
+    pub amud: Amud,
+}
+
+impl Daf {
+    /// Sefaria stores amudim sequentially starting at `1a`, so `1a` is section 1, `1b` is 2 and
+    /// `2a` (where every tractate actually starts) is 3.
+    pub fn index(&self) -> usize {
+        (self.daf - 1) * 2
+            + match self.amud {
+                Amud::A => 1,
+                Amud::B => 2,
+            }
+    }
+
+    /// Inverse of [`Daf::index`], `0` is not a valid index.
+    pub fn from_index(index: usize) -> Option<Daf> {
+        if index == 0 {
+            return None;
+        }
+        Some(Daf {
+            daf: index.div_ceil(2),
+            amud: if index % 2 == 1 { Amud::A } else { Amud::B },
+        })
+    }
+}
+
+impl FromStr for Daf {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, amud) = s.split_at(s.len().saturating_sub(1));
+        let amud = match amud {
+            "a" | "A" => Amud::A,
+            "b" | "B" => Amud::B,
+            _ => return Err(anyhow!("Daf must end with 'a' or 'b': {}", s)),
+        };
+        let daf: usize = number
+            .parse()
+            .map_err(|_| anyhow!("Could not parse daf number: {}", s))?;
+        if daf == 0 || number.starts_with('0') {
+            return Err(anyhow!("Daf starts with '0' which is forbidden: {}", s));
+        }
+        Ok(Daf { daf, amud })
+    }
+}
+
+impl fmt::Display for Daf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let amud = match self.amud {
+            Amud::A => 'a',
+            Amud::B => 'b',
+        };
+        write!(f, "{}{}", self.daf, amud)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amud_to_index() {
+        assert_eq!("1a".parse::<Daf>().unwrap().index(), 1);
+        assert_eq!("2a".parse::<Daf>().unwrap().index(), 3);
+        assert_eq!("31b".parse::<Daf>().unwrap().index(), 62);
+    }
+
+    #[test]
+    fn index_to_amud() {
+        assert_eq!(
+            Daf::from_index(3),
+            Some(Daf {
+                daf: 2,
+                amud: Amud::A
+            })
+        );
+        assert_eq!(
+            Daf::from_index(62),
+            Some(Daf {
+                daf: 31,
+                amud: Amud::B
+            })
+        );
+        assert_eq!(Daf::from_index(0), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        for idx in 1..400 {
+            assert_eq!(Daf::from_index(idx).unwrap().index(), idx);
+        }
+    }
+
+    #[test]
+    fn bad_dafs() {
+        assert!("2c".parse::<Daf>().is_err());
+        assert!("0a".parse::<Daf>().is_err());
+        assert!("a".parse::<Daf>().is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::anyhow;
+use regex::{Regex, RegexBuilder};
+
+use super::hebrew::{fold_finals, is_hebrew_letter, strip_nikkud, strip_nikkud_mapped};
+use super::library::CachedBook;
+use super::text::strip_tags;
+
+/// A word with the letters of `root` in it, in order, with anything before and after. Vav and
+/// yod can come between the letters since they are often added when a root is conjugated, like
+/// `שומר` from `שמר`.
+pub fn root_pattern(root: &str) -> Result<String, anyhow::Error> {
+    let letters: Vec<char> = fold_finals(&strip_nikkud(root))
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if letters.len() != 3 || !letters.iter().all(|c| is_hebrew_letter(*c)) {
+        return Err(anyhow!("'{}' is not a three letter Hebrew root", root));
+    }
+    Ok(format!(
+        r"\b[א-ת]*{}[וי]?{}[וי]?{}[א-ת]*\b",
+        letters[0], letters[1], letters[2]
+    ))
+}
+
+pub fn build_regex(
+    pattern: &str,
+    root: bool,
+    ignore_nikkud: bool,
+    ignore_case: bool,
+) -> Result<Regex, anyhow::Error> {
+    let pattern = if root {
+        root_pattern(pattern)?
+    } else if ignore_nikkud {
+        // Otherwise a pattern with nikkud could never match
+        strip_nikkud(pattern)
+    } else {
+        pattern.to_string()
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|err| anyhow!("Could not use '{}' as a pattern: {}", pattern, err))
+}
+
+/// `text` with every match in inline code, or `None` if nothing matched. Without nikkud, matches
+/// are found in the text stripped of nikkud and ta'amim (and final letters, for roots), and then
+/// highlighted in the text as it is.
+fn highlight_matches(text: &str, regex: &Regex, ignore_nikkud: bool, root: bool) -> Option<String> {
+    let (searched, offsets) = if ignore_nikkud || root {
+        let (stripped, offsets) = strip_nikkud_mapped(text);
+        // Final letters are the same length as the usual ones, so the offsets still hold
+        let stripped = if root {
+            fold_finals(&stripped)
+        } else {
+            stripped
+        };
+        (stripped, offsets)
+    } else {
+        (text.to_string(), (0..=text.len()).collect())
+    };
+
+    let mut highlighted = String::with_capacity(text.len());
+    let mut last = 0;
+    for found in regex.find_iter(&searched) {
+        if found.is_empty() {
+            continue;
+        }
+        let (start, end) = (offsets[found.start()], offsets[found.end()]);
+        highlighted.push_str(&text[last..start]);
+        highlighted.push_str(&format!("`{}`", &text[start..end]));
+        last = end;
+    }
+    if last == 0 {
+        return None;
+    }
+    highlighted.push_str(&text[last..]);
+    Some(highlighted)
+}
+
+/// Every segment of `books` that matches, with what matched highlighted.
+pub fn grep_books(books: &[CachedBook], regex: &Regex, ignore_nikkud: bool, root: bool) -> String {
+    let mut formatted_string = vec![];
+    for book in books {
+        for segment in &book.segments {
+            for text in [&segment.he, &segment.en] {
+                if let Some(highlighted) =
+                    highlight_matches(&strip_tags(text), regex, ignore_nikkud, root)
+                {
+                    formatted_string.push(format!("**{}** ~ {}", segment.reference, highlighted));
+                }
+            }
+        }
+    }
+
+    let count = formatted_string.len();
+    formatted_string.insert(
+        0,
+        format!(
+            "**{} matching segment{}**\n---",
+            count,
+            if count == 1 { "" } else { "s" }
+        ),
+    );
+    formatted_string.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_roots() {
+        let regex = build_regex("שָׁמַר", true, false, false).unwrap();
+        assert_eq!(
+            highlight_matches("וַיִּשְׁמֹר אֶת הַדָּבָר", &regex, false, true).unwrap(),
+            "`וַיִּשְׁמֹר` אֶת הַדָּבָר"
+        );
+        assert!(highlight_matches("שומרים", &regex, false, true).is_some());
+        assert!(highlight_matches("משמרתם", &regex, false, true).is_some());
+        assert!(highlight_matches("שמים", &regex, false, true).is_none());
+        assert!(build_regex("שמ", true, false, false).is_err());
+    }
+
+    #[test]
+    fn matches_without_nikkud() {
+        let regex = build_regex("בָּרָא", false, true, false).unwrap();
+        assert!(highlight_matches("בָּרָ֣א", &regex, false, false).is_none());
+        assert!(highlight_matches("ברא", &regex, false, false).is_some());
+        assert_eq!(
+            highlight_matches("בְּרֵאשִׁ֖ית בָּרָ֣א", &regex, true, false).unwrap(),
+            "`בְּרֵא`שִׁ֖ית `בָּרָ֣א`"
+        );
+    }
+}
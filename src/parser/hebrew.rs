@@ -0,0 +1,87 @@
+// Hebrew text on Sefaria comes with and without nikkud (vowel points) and ta'amim (cantillation
+// marks), so anything that compares Hebrew has to get rid of them first.
+
+/// Ta'amim, U+0591 to U+05AF.
+pub fn is_taam(c: char) -> bool {
+    ('\u{0591}'..='\u{05AF}').contains(&c)
+}
+
+/// Nikkud, including the dots for shin/sin and dagesh.
+pub fn is_nikkud(c: char) -> bool {
+    matches!(
+        c,
+        '\u{05B0}'
+            ..='\u{05BD}'
+                | '\u{05BF}'
+                | '\u{05C1}'
+                | '\u{05C2}'
+                | '\u{05C4}'
+                | '\u{05C5}'
+                | '\u{05C7}'
+    )
+}
+
+pub fn is_hebrew_letter(c: char) -> bool {
+    ('\u{05D0}'..='\u{05EA}').contains(&c)
+}
+
+pub fn is_hebrew(text: &str) -> bool {
+    text.chars().any(is_hebrew_letter)
+}
+
+/// Takes out nikkud and ta'amim, and turns a maqaf into a space since it joins separate words.
+pub fn strip_nikkud(text: &str) -> String {
+    text.chars()
+        .filter(|c| !is_nikkud(*c) && !is_taam(*c))
+        .map(|c| if c == '\u{05BE}' { ' ' } else { c })
+        .collect()
+}
+
+/// Like [`strip_nikkud`], along with where every byte of the result starts in `text` and then
+/// where `text` ends, so that matches can be found without nikkud and shown with it.
+pub fn strip_nikkud_mapped(text: &str) -> (String, Vec<usize>) {
+    let mut stripped = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    for (idx, c) in text.char_indices() {
+        if is_nikkud(c) || is_taam(c) {
+            continue;
+        }
+        let c = if c == '\u{05BE}' { ' ' } else { c };
+        stripped.push(c);
+        offsets.extend(std::iter::repeat_n(idx, c.len_utf8()));
+    }
+    offsets.push(text.len());
+    (stripped, offsets)
+}
+
+/// Turns final letters into their usual forms, so a word matches whether or not it was cut off
+/// by a suffix.
+pub fn fold_finals(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ך' => 'כ',
+            'ם' => 'מ',
+            'ן' => 'נ',
+            'ף' => 'פ',
+            'ץ' => 'צ',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_nikkud_and_taamim() {
+        assert_eq!(strip_nikkud("בְּרֵאשִׁ֖ית בָּרָ֣א"), "בראשית ברא");
+        assert_eq!(strip_nikkud("עַל־פְּנֵי"), "על פני");
+        assert_eq!(strip_nikkud("two spines"), "two spines");
+    }
+
+    #[test]
+    fn folds_final_letters() {
+        assert_eq!(fold_finals("שלום מלך"), "שלומ מלכ");
+    }
+}
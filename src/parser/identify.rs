@@ -0,0 +1,177 @@
+use anyhow::anyhow;
+
+use super::hebrew::strip_nikkud;
+use super::keyword::{normalize_word, search_wrapper, Hit, Mode, Query};
+use super::search::download_passage;
+use super::text::{convert_to_text, strip_tags};
+
+// Longer quotes are searched a window of words at a time, since a sheet might quote a little
+// more or less than one segment
+static WINDOW: usize = 8;
+// Candidates that get their whole text downloaded to be checked
+static CHECKED: usize = 5;
+
+/// Where a quotation might be from, and how much of it was found there.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub reference: String,
+    pub version: String,
+    pub lang: String,
+    // How much of the quotation is in the text, in order, from 0 to 1
+    pub confidence: f64,
+    pub text: String,
+}
+
+/// The words of a text as they are compared, without nikkud, punctuation or case.
+fn words(text: &str) -> Vec<String> {
+    strip_nikkud(&strip_tags(text))
+        .split_whitespace()
+        .map(normalize_word)
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// How many words of `quote` show up in `text` in the same order, which is forgiving of words
+/// being left out or added on either side.
+fn overlap(quote: &[String], text: &[String]) -> usize {
+    let mut previous = vec![0; text.len() + 1];
+    for quoted in quote {
+        let mut current = vec![0; text.len() + 1];
+        for (idx, word) in text.iter().enumerate() {
+            current[idx + 1] = if quoted == word {
+                previous[idx] + 1
+            } else {
+                previous[idx + 1].max(current[idx])
+            };
+        }
+        previous = current;
+    }
+    previous[text.len()]
+}
+
+/// The start, middle and end of a quote, or all of it if it's short.
+fn windows(quote: &[String]) -> Vec<String> {
+    if quote.len() <= WINDOW {
+        return vec![quote.join(" ")];
+    }
+    let mut starts = vec![0, (quote.len() - WINDOW) / 2, quote.len() - WINDOW];
+    starts.dedup();
+    starts
+        .into_iter()
+        .map(|start| quote[start..start + WINDOW].join(" "))
+        .collect()
+}
+
+fn candidate(hit: &Hit, quote: &[String]) -> Candidate {
+    let hit_ref = hit.hit_ref();
+    let text = hit
+        .highlight
+        .fragments()
+        .map(|fragment| strip_tags(fragment))
+        .collect::<Vec<_>>()
+        .join(" … ");
+    Candidate {
+        confidence: overlap(quote, &words(&text)) as f64 / quote.len() as f64,
+        reference: hit_ref.reference,
+        version: hit_ref.version,
+        lang: hit_ref.lang,
+        text,
+    }
+}
+
+/// Checks a candidate against its whole text rather than the fragments the search returned.
+fn check(candidate: &mut Candidate, quote: &[String], parameters: Vec<(&str, &str)>) {
+    let (version_parameter, field) = if candidate.lang == "he" {
+        ("vhe", "he")
+    } else {
+        ("ven", "text")
+    };
+    let mut parameters = parameters;
+    parameters.push((version_parameter, &candidate.version));
+    let Ok(parsed_json) = download_passage(&candidate.reference, parameters) else {
+        return;
+    };
+    if let Ok(text) = convert_to_text(&parsed_json[field]) {
+        let text = strip_tags(&text.join(" "));
+        let confidence = overlap(quote, &words(&text)) as f64 / quote.len() as f64;
+        if confidence >= candidate.confidence {
+            candidate.confidence = confidence;
+            candidate.text = text;
+        }
+    }
+}
+
+/// Searches for a quotation and ranks every ref it could be from, most likely first.
+pub fn identify(
+    quotation: &str,
+    parameters: Vec<(&str, &str)>,
+) -> Result<Vec<Candidate>, anyhow::Error> {
+    let quote = words(quotation);
+    if quote.is_empty() {
+        return Err(anyhow!("There is nothing to identify"));
+    }
+
+    let mut candidates: Vec<Candidate> = vec![];
+    for window in windows(&quote) {
+        let query = Query::with_mode(&window, 20, Mode::Phrase, None);
+        for hit in search_wrapper(&query, parameters.clone())?.hits.hits {
+            let candidate = candidate(&hit, &quote);
+            match candidates
+                .iter_mut()
+                .find(|found| found.reference == candidate.reference)
+            {
+                Some(found) if found.confidence >= candidate.confidence => (),
+                Some(found) => *found = candidate,
+                None => candidates.push(candidate),
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    for candidate in candidates.iter_mut().take(CHECKED) {
+        check(candidate, &quote, parameters.clone());
+    }
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    Ok(candidates)
+}
+
+/// The best candidate with its text, then the runners up.
+pub fn format_candidates(candidates: &[Candidate], count: usize) -> String {
+    let Some(best) = candidates.first() else {
+        return "**No source found**".to_string();
+    };
+    let mut formatted_string = vec![format!(
+        "# {} ~ {:.0}%\n*{}*\n> {}",
+        best.reference,
+        best.confidence * 100.0,
+        best.version,
+        best.text
+    )];
+    if candidates.len() > 1 && count > 1 {
+        formatted_string.push("---\n**Other candidates**".to_string());
+    }
+    for candidate in candidates.iter().skip(1).take(count.saturating_sub(1)) {
+        formatted_string.push(format!(
+            "* {} ~ {:.0}%",
+            candidate.reference,
+            candidate.confidence * 100.0
+        ));
+    }
+    formatted_string.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_words() {
+        let quote = words("In the beginning God created");
+        assert_eq!(
+            overlap(&quote, &words("In the beginning, God created the heaven")),
+            5
+        );
+        assert_eq!(overlap(&quote, &words("God created, in the beginning")), 3);
+        assert_eq!(overlap(&words("בְּרֵאשִׁית בָּרָא"), &words("בראשית ברא אלהים")), 2);
+    }
+}
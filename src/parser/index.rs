@@ -0,0 +1,433 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use super::hebrew::{fold_finals, strip_nikkud};
+use super::keyword::{
+    Aggregation, Aggregations, Bucket, Highlight, Hit, Hits, Query, Root, Source,
+};
+use super::library::CachedBook;
+use super::text::strip_tags;
+use super::toc::{books, in_path, Toc};
+
+static INDEX_FILE: &str = "index.json";
+// Words shown on either side of a match in a snippet
+static SNIPPET_WORDS: usize = 8;
+
+/// One segment in one language, which is what a local hit points to.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    #[serde(rename = "ref")]
+    pub reference: String,
+    pub version: String,
+    pub lang: String,
+    pub path: String,
+    pub text: String,
+}
+
+/// Which documents every (stemmed) word is in. Documents are in table of contents order, so
+/// sorting hits canonically is sorting them by id.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalIndex {
+    pub documents: Vec<Document>,
+    pub terms: BTreeMap<String, Vec<u32>>,
+}
+
+/// A word of a text, by where it is and what it is compared as.
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    start: usize,
+    end: usize,
+    // Without nikkud, final letters or case
+    word: String,
+    stem: String,
+}
+
+/// Cuts a word down to what its other forms have in common, so `blessed`, `blessing` and
+/// `blesses` are all `bless`. This is much cruder than a real stemmer, but it only has to agree
+/// with itself.
+fn stem(word: &str) -> String {
+    if word.len() <= 3 || !word.chars().all(|c| c.is_ascii_lowercase()) {
+        return word.to_string();
+    }
+    let mut stem = word.to_string();
+    for (suffix, replacement) in [
+        ("sses", "ss"),
+        ("ies", "y"),
+        ("ness", ""),
+        ("ment", ""),
+        ("ation", "ate"),
+        ("ings", ""),
+        ("ing", ""),
+        ("edly", ""),
+        ("ed", ""),
+        ("ly", ""),
+        ("es", ""),
+        ("s", ""),
+    ] {
+        if suffix == "s" && (stem.ends_with("ss") || stem.ends_with("us") || stem.ends_with("is")) {
+            continue;
+        }
+        if let Some(rest) = stem.strip_suffix(suffix) {
+            if rest.len() >= 3 {
+                stem = format!("{}{}", rest, replacement);
+                break;
+            }
+        }
+    }
+    // `hopping` and `hoped` should both end up as `hop`
+    let bytes = stem.as_bytes();
+    if bytes.len() > 3
+        && bytes[bytes.len() - 1] == bytes[bytes.len() - 2]
+        && !b"lsz".contains(&bytes[bytes.len() - 1])
+    {
+        stem.pop();
+    }
+    if stem.len() > 3 && stem.ends_with('e') {
+        stem.pop();
+    }
+    stem
+}
+
+/// Splits text into words, which a maqaf also does.
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (idx, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        if c.is_whitespace() || c == '\u{05BE}' {
+            if let Some(start) = start.take() {
+                let word = fold_finals(&strip_nikkud(&text[start..idx]))
+                    .trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase();
+                if !word.is_empty() {
+                    tokens.push(Token {
+                        start,
+                        end: idx,
+                        stem: stem(&word),
+                        word,
+                    });
+                }
+            }
+        } else if start.is_none() {
+            start = Some(idx);
+        }
+    }
+    tokens
+}
+
+impl LocalIndex {
+    /// Indexes every segment of every cached book, in both languages.
+    pub fn build(mut cached: Vec<CachedBook>, toc: &Toc) -> LocalIndex {
+        let order: HashMap<&str, usize> = books(toc)
+            .iter()
+            .enumerate()
+            .map(|(idx, book)| (book.title.as_str(), idx))
+            .collect();
+        cached.sort_by_key(|book| {
+            order
+                .get(book.title.as_str())
+                .copied()
+                .unwrap_or(usize::MAX)
+        });
+
+        let mut index = LocalIndex::default();
+        for book in cached {
+            for segment in &book.segments {
+                for (lang, text, version) in [
+                    ("en", &segment.en, &book.version),
+                    ("he", &segment.he, &book.he_version),
+                ] {
+                    if text.is_empty() {
+                        continue;
+                    }
+                    let id = index.documents.len() as u32;
+                    let text = strip_tags(text);
+                    for token in tokenize(&text) {
+                        let postings = index.terms.entry(token.stem).or_default();
+                        if postings.last() != Some(&id) {
+                            postings.push(id);
+                        }
+                    }
+                    index.documents.push(Document {
+                        reference: segment.reference.clone(),
+                        version: version.clone(),
+                        lang: lang.to_string(),
+                        path: book.path.clone(),
+                        text,
+                    });
+                }
+            }
+        }
+        index
+    }
+
+    pub fn save(&self, xdg_dirs: &xdg::BaseDirectories) -> Result<(), anyhow::Error> {
+        fs::write(
+            xdg_dirs.place_data_file(INDEX_FILE)?,
+            serde_json::to_string(self)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn load(xdg_dirs: &xdg::BaseDirectories) -> Result<LocalIndex, anyhow::Error> {
+        let path = xdg_dirs.find_data_file(INDEX_FILE).ok_or(anyhow!(
+            "There is no local index yet, cache a book with `rsbbi index add` first"
+        ))?;
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Runs a query the way the search-wrapper would, as far as it can be done offline.
+    pub fn search(&self, query: &Query) -> Result<Root, anyhow::Error> {
+        if query.sort_fields.iter().any(|field| field == "comp_date") {
+            return Err(anyhow!(
+                "The local index doesn't know when books were written, so it can't sort by it"
+            ));
+        }
+        let exact = query.field == "exact";
+        let wanted = tokenize(&query.query);
+        let Some(first) = wanted.first() else {
+            return Ok(Root::default());
+        };
+
+        // Documents with every word of the query in them
+        let mut candidates = self.terms.get(&first.stem).cloned().unwrap_or_default();
+        for token in &wanted[1..] {
+            let postings = self.terms.get(&token.stem).map_or(&[][..], Vec::as_slice);
+            candidates.retain(|id| postings.binary_search(id).is_ok());
+        }
+        let idf: f64 = wanted
+            .iter()
+            .map(|token| {
+                let count = self.terms.get(&token.stem).map_or(0, Vec::len).max(1);
+                (self.documents.len() as f64 / count as f64).ln() + 1.0
+            })
+            .sum();
+
+        let mut found = vec![];
+        for id in candidates {
+            let document = &self.documents[id as usize];
+            if !query.filters.is_empty()
+                && !query
+                    .filters
+                    .iter()
+                    .any(|filter| in_path(&document.path, filter))
+            {
+                continue;
+            }
+            let tokens = tokenize(&document.text);
+            let matches = find_matches(&tokens, &wanted, exact, query.slop.max(0) as usize);
+            if matches.is_empty() {
+                continue;
+            }
+            let score = matches.len() as f64 * idf / (tokens.len() as f64).sqrt();
+            found.push((id, score, document, tokens, matches));
+        }
+        if query.sort_fields.is_empty() {
+            found.sort_by(|a, b| b.1.total_cmp(&a.1));
+        }
+
+        let mut results = Root::default();
+        if query.aggs.iter().any(|agg| agg == "path") {
+            let mut counts: BTreeMap<&str, i64> = BTreeMap::new();
+            for (_, _, document, _, _) in &found {
+                *counts.entry(&document.path).or_default() += 1;
+            }
+            results.aggregations = Aggregations {
+                path: Aggregation {
+                    buckets: counts
+                        .into_iter()
+                        .map(|(key, doc_count)| Bucket {
+                            key: key.to_string(),
+                            doc_count,
+                        })
+                        .collect(),
+                },
+            };
+        }
+        results.hits = Hits {
+            total: found.len() as i64,
+            max_score: found.iter().map(|(_, score, ..)| *score).reduce(f64::max),
+            hits: found
+                .into_iter()
+                .skip(query.start.max(0) as usize)
+                .take(query.size.max(0) as usize)
+                .map(|(_, score, document, tokens, matches)| Hit {
+                    index: "local".to_string(),
+                    id: format!(
+                        "{} ({} [{}])",
+                        document.reference, document.version, document.lang
+                    ),
+                    score: Some(score),
+                    source: Source {
+                        reference: document.reference.clone(),
+                        version: document.version.clone(),
+                        lang: document.lang.clone(),
+                        path: document.path.clone(),
+                        ..Source::default()
+                    },
+                    highlight: Highlight {
+                        exact: snippets(&document.text, &tokens, &matches),
+                        ..Highlight::default()
+                    },
+                })
+                .collect(),
+        };
+        Ok(results)
+    }
+}
+
+/// Every place the words of `wanted` show up in order, with no more than `slop` words between
+/// each of them, as the positions of the tokens that matched.
+fn find_matches(tokens: &[Token], wanted: &[Token], exact: bool, slop: usize) -> Vec<Vec<usize>> {
+    let same = |token: &Token, wanted: &Token| {
+        if exact {
+            token.word == wanted.word
+        } else {
+            token.stem == wanted.stem
+        }
+    };
+    let mut matches = vec![];
+    let mut idx = 0;
+    while idx < tokens.len() {
+        if !same(&tokens[idx], &wanted[0]) {
+            idx += 1;
+            continue;
+        }
+        let mut positions = vec![idx];
+        for word in &wanted[1..] {
+            let last = *positions.last().unwrap();
+            let window = (last + 1)..tokens.len().min(last + slop + 2);
+            match window
+                .into_iter()
+                .find(|position| same(&tokens[*position], word))
+            {
+                Some(position) => positions.push(position),
+                None => break,
+            }
+        }
+        if positions.len() == wanted.len() {
+            idx = *positions.last().unwrap() + 1;
+            matches.push(positions);
+        } else {
+            idx += 1;
+        }
+    }
+    matches
+}
+
+/// A few words on either side of every match (up to three), with the matched words in `<b>` like
+/// the search-wrapper does.
+fn snippets(text: &str, tokens: &[Token], matches: &[Vec<usize>]) -> Vec<String> {
+    matches
+        .iter()
+        .take(3)
+        .map(|positions| {
+            let first = positions[0].saturating_sub(SNIPPET_WORDS);
+            let last = (positions[positions.len() - 1] + SNIPPET_WORDS).min(tokens.len() - 1);
+            let mut snippet = String::new();
+            let mut from = tokens[first].start;
+            for position in positions {
+                let token = &tokens[*position];
+                snippet.push_str(&text[from..token.start]);
+                snippet.push_str(&format!("<b>{}</b>", &text[token.start..token.end]));
+                from = token.end;
+            }
+            snippet.push_str(&text[from..tokens[last].end]);
+            format!(
+                "{}{}{}",
+                if first > 0 { "…" } else { "" },
+                snippet,
+                if last < tokens.len() - 1 { "…" } else { "" }
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::library::CachedSegment;
+
+    fn index() -> LocalIndex {
+        LocalIndex::build(
+            vec![CachedBook {
+                title: "Genesis".to_string(),
+                path: "Tanakh/Torah/Genesis".to_string(),
+                version: "JPS".to_string(),
+                segments: vec![
+                    CachedSegment {
+                        reference: "Genesis 1:1".to_string(),
+                        en: "When God began to create heaven and earth".to_string(),
+                        he: "בְּרֵאשִׁ֖ית בָּרָ֣א אֱלֹהִ֑ים".to_string(),
+                    },
+                    CachedSegment {
+                        reference: "Genesis 1:22".to_string(),
+                        en: "God <b>blessed</b> them, saying".to_string(),
+                        he: String::default(),
+                    },
+                ],
+                ..CachedBook::default()
+            }],
+            &Toc::default(),
+        )
+    }
+
+    #[test]
+    fn stems_english() {
+        assert_eq!(stem("blessed"), "bless");
+        assert_eq!(stem("blessing"), "bless");
+        assert_eq!(stem("blesses"), "bless");
+        assert_eq!(stem("hoping"), stem("hoped"));
+        assert_eq!(stem("hopping"), "hop");
+        assert_eq!(stem("cities"), "city");
+    }
+
+    #[test]
+    fn searches_locally() {
+        let index = index();
+        let results = index.search(&Query::new("ברא", 10)).unwrap();
+        assert_eq!(results.hits.total, 1);
+        assert_eq!(results.hits.hits[0].id, "Genesis 1:1 ( [he])");
+
+        // Exact searches don't stem, phrase searches do
+        assert_eq!(
+            index
+                .search(&Query::new("blessing", 10))
+                .unwrap()
+                .hits
+                .total,
+            0
+        );
+        let mut query = Query::new("blessing", 10);
+        query.field = "naive_lemmatizer".to_string();
+        let results = index.search(&query).unwrap();
+        assert_eq!(
+            results.hits.hits[0].highlight.exact,
+            ["God <b>blessed</b> them, saying"]
+        );
+
+        assert_eq!(
+            index
+                .search(&Query::new("create heaven", 10))
+                .unwrap()
+                .hits
+                .total,
+            1
+        );
+        assert_eq!(
+            index
+                .search(&Query::new("create earth", 10))
+                .unwrap()
+                .hits
+                .total,
+            0
+        );
+        let mut query = Query::new("create earth", 10);
+        query.slop = 2;
+        assert_eq!(index.search(&query).unwrap().hits.total, 1);
+    }
+}
@@ -1,31 +1,277 @@
 use anyhow::anyhow;
+use serde_json::Value;
 
-use super::{bible_verse::ReturnedBibleVerse, shape::Shape};
+use super::{
+    bible_verse::{parse_verse, BibleRange, Point, ReturnedBibleVerse, Section},
+    daf::Daf,
+    keyword::thousands,
+    metadata::IndexRecord,
+    shape::{Shape, ShapeContents},
+    text::{convert_to_text, strip_tags},
+    toc::TocBook,
+};
 
+/// Sections and segments in a book, where only amudim that exist count as sections.
+fn counts(shape: &ShapeContents) -> (i64, i64) {
+    let sections = if shape.is_daf_based() {
+        shape.chapters.iter().filter(|lines| **lines > 0).count()
+    } else {
+        shape.chapters.len()
+    };
+    (sections as i64, shape.chapters.iter().sum())
+}
+
+/// A table of every book in a category with how long it is and whether it's cached, then totals.
+pub fn category_info(path: &str, books: &[&TocBook], shapes: &Shape, cached: &[String]) -> String {
+    let shapes: Vec<Option<&ShapeContents>> = books
+        .iter()
+        .map(|book| shapes.iter().find(|shape| shape.title == book.title))
+        .collect();
+    let daf_based = shapes
+        .iter()
+        .flatten()
+        .filter(|shape| shape.is_daf_based())
+        .count();
+    let (section_name, segment_name) = match daf_based {
+        0 => ("Chapters", "Verses"),
+        all if all == shapes.iter().flatten().count() => ("Amudim", "Lines"),
+        _ => ("Sections", "Segments"),
+    };
+
+    let mut table = vec![
+        format!("# {}", path),
+        format!("**{}** books", books.len()),
+        "|:-|-:|-:|:-:|".to_string(),
+        format!(
+            "|**Book**|**{}**|**{}**|**Cached**|",
+            section_name, segment_name
+        ),
+        "|:-|-:|-:|:-:|".to_string(),
+    ];
+    let (mut total_sections, mut total_segments, mut total_cached) = (0, 0, 0);
+    for (book, shape) in books.iter().zip(shapes) {
+        let is_cached = cached.contains(&book.title);
+        total_cached += is_cached as usize;
+        let (sections, segments) = match shape {
+            Some(shape) => {
+                let (sections, segments) = counts(shape);
+                total_sections += sections;
+                total_segments += segments;
+                (thousands(sections), thousands(segments))
+            }
+            None => ("?".to_string(), "?".to_string()),
+        };
+        table.push(format!(
+            "|{}|{}|{}|{}|",
+            book.title,
+            sections,
+            segments,
+            if is_cached { "✓" } else { "" }
+        ));
+    }
+    table.push("|-|-|-|-|".to_string());
+    table.push(format!(
+        "|**Total**|**{}**|**{}**|**{}/{}**|",
+        thousands(total_sections),
+        thousands(total_segments),
+        total_cached,
+        books.len()
+    ));
+    table.push("|-".to_string());
+    table.join("\n")
+}
+
+/// The start of a line of text, so that it fits on one line.
+fn opening(text: &str) -> String {
+    let text = strip_tags(text);
+    match text.char_indices().nth(80) {
+        Some((idx, _)) => format!("{}…", &text[..idx]),
+        None => text,
+    }
+}
+
+/// Words in the English and Hebrew of a passage.
+fn word_count(passage: &Value) -> String {
+    let count = |field: &str| -> usize {
+        convert_to_text(&passage[field])
+            .unwrap_or_default()
+            .iter()
+            .map(|line| strip_tags(line).split_whitespace().count())
+            .sum()
+    };
+    format!(
+        "> Words: **{}** English, **{}** Hebrew",
+        thousands(count("text") as i64),
+        thousands(count("he") as i64)
+    )
+}
+
+/// Every part of the book's other structures that overlaps `from` to `to`, like the parasha of
+/// a chapter along with which of its aliyot the chapter is in.
+fn alt_locations(record: &IndexRecord, from: Point, to: Point) -> Vec<String> {
+    let bounds = |reference: &str| parse_verse(reference).ok()?.bounds().ok()?;
+    let mut locations = vec![];
+    for (name, alt) in &record.alt_structs {
+        for node in &alt.nodes {
+            let Some((start, end)) = bounds(&node.whole_ref) else {
+                continue;
+            };
+            if start > to || end < from {
+                continue;
+            }
+            let mut location = format!("> {}: **{}**", name, node.name());
+            if name == "Parasha" {
+                let aliyot: Vec<String> = node
+                    .refs
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(idx, reference)| {
+                        let (start, end) = bounds(reference)?;
+                        (start <= to && end >= from).then(|| (idx + 1).to_string())
+                    })
+                    .collect();
+                if !aliyot.is_empty() {
+                    location.push_str(&format!(" ~ aliyah {}", aliyot.join(", ")));
+                }
+            }
+            locations.push(location);
+        }
+    }
+    locations
+}
+
+/// Shows how long a book is, or for a section or verse, what's in it and where it falls.
+/// `passage` is the text of whatever was asked for and `record` the book's index record, both
+/// only used for sections and verses.
 pub fn handle_info(
     index: &Shape,
-    _book: &str,
     info: &ReturnedBibleVerse,
+    passage: Option<&Value>,
+    record: Option<&IndexRecord>,
 ) -> Result<String, anyhow::Error> {
     let mut info_vec: Vec<String> = vec![];
-    if info.section.is_some() {
-        for section in index {
-            info_vec.push(format!(
-                "## **{}** ~ **{}**\n> Verses: **{}**",
-                section.title,
-                info.section.as_ref().unwrap(),
-                section.chapters.get::<usize>(info.section.as_ref().unwrap().parse::<usize>().unwrap() - 1).unwrap(),
-            ));
+    if let Some(section) = &info.section {
+        let parsed_section: Section = section.parse()?;
+        let (verse_name, section_name) = match parsed_section {
+            Section::Chapter(_) => ("Verse", "chapter"),
+            Section::Daf(_) => ("Line", "amud"),
+        };
+        let contents = index.first().ok_or(anyhow!(
+            "Sefaria does not know of a book called '{}'",
+            info.book
+        ))?;
+        let length = contents
+            .chapters
+            .get(parsed_section.index() - 1)
+            .filter(|length| **length > 0)
+            .ok_or(anyhow!(
+                "{} does not have a {} {}",
+                contents.title,
+                section_name,
+                section
+            ))?;
+        let length = *length as usize;
+
+        // Everything about a section or verse goes under one heading
+        let mut lines: Vec<String> = vec![];
+        match &info.verse {
+            None => {
+                lines.push(format!(
+                    "## **{}** ~ **{}**\n> {}s: **{}**",
+                    contents.title, section, verse_name, length
+                ));
+                if let Some(passage) = passage {
+                    let text = convert_to_text(&passage["text"])
+                        .ok()
+                        .filter(|text| !text.is_empty())
+                        .or(convert_to_text(&passage["he"]).ok())
+                        .unwrap_or_default();
+                    if let (Some(first), Some(last)) = (text.first(), text.last()) {
+                        lines.push(format!(
+                            "> First: *{}*\n> Last: *{}*",
+                            opening(first),
+                            opening(last)
+                        ));
+                    }
+                    lines.push(word_count(passage));
+                }
+                if let Some(record) = record {
+                    let index = parsed_section.index();
+                    lines.extend(alt_locations(record, (index, 1), (index, usize::MAX)));
+                }
+            }
+            Some(BibleRange::Number(verse)) => {
+                if *verse == 0 || *verse > length {
+                    return Err(anyhow!(
+                        "{} {} only has {} {}s",
+                        contents.title,
+                        section,
+                        length,
+                        verse_name.to_lowercase()
+                    ));
+                }
+                let before: i64 = contents.chapters[..parsed_section.index() - 1].iter().sum();
+                let total: i64 = contents.chapters.iter().sum();
+                let position = before + *verse as i64;
+                lines.push(format!(
+                    "## **{}** ~ **{}:{}**\n> {} **{}** of **{}** in {} {}\n> {} **{}** of **{}** in {} (**{:.1}%**)",
+                    contents.title,
+                    section,
+                    verse,
+                    verse_name,
+                    verse,
+                    length,
+                    section_name,
+                    section,
+                    verse_name.to_lowercase(),
+                    thousands(position),
+                    thousands(total),
+                    contents.title,
+                    position as f64 * 100.0 / total.max(1) as f64
+                ));
+                if let Some(passage) = passage {
+                    lines.push(word_count(passage));
+                }
+                if let Some(record) = record {
+                    let point = (parsed_section.index(), *verse);
+                    lines.extend(alt_locations(record, point, point));
+                }
+            }
+            Some(_) => {
+                return Err(anyhow!(
+                    "Info can only be shown for a book, a {} or a single {}",
+                    section_name,
+                    verse_name.to_lowercase()
+                ))
+            }
         }
+        info_vec.push(lines.join("\n"));
+    } else if info.verse.is_some() {
+        return Err(anyhow!(
+            "Info can only be shown for a book, a section or a single verse"
+        ));
     } else {
         for section in index {
-            info_vec.push(format!(
-                "## **{}** ~ **{}**\n> Chapters: **{}**\n> Verses: **{}**",
-                section.title,
-                section.section,
-                section.length,
-                section.chapters.iter().sum::<i64>(),
-            ));
+            if section.is_daf_based() {
+                info_vec.push(format!(
+                    "## **{}** ~ **{}**\n> Amudim: **{}**\n> Last daf: **{}**\n> Lines: **{}**",
+                    section.title,
+                    section.section,
+                    section.chapters.iter().filter(|lines| **lines > 0).count(),
+                    Daf::from_index(section.chapters.len())
+                        .map(|daf| daf.to_string())
+                        .unwrap_or_default(),
+                    section.chapters.iter().sum::<i64>(),
+                ));
+            } else {
+                info_vec.push(format!(
+                    "## **{}** ~ **{}**\n> Chapters: **{}**\n> Verses: **{}**",
+                    section.title,
+                    section.section,
+                    section.length,
+                    section.chapters.iter().sum::<i64>(),
+                ));
+            }
         }
     }
     if info_vec.is_empty() {
@@ -36,3 +282,39 @@ pub fn handle_info(
         Ok(info_vec.join("\n---\n").to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn section_and_verse_info() {
+        let shape: Shape = serde_json::from_str(
+            r#"[{"section": "Tanakh", "heTitle": "", "title": "Genesis", "length": 2,
+                 "chapters": [31, 25], "book": "Genesis", "heBook": ""}]"#,
+        )
+        .unwrap();
+        let record: IndexRecord = serde_json::from_str(
+            r#"{"title": "Genesis", "alt_structs": {"Parasha": {"nodes": [{
+                "sharedTitle": "Bereshit", "wholeRef": "Genesis 1:1-2:25",
+                "refs": ["Genesis 1:1-1:31", "Genesis 2:1-25"]}]}}}"#,
+        )
+        .unwrap();
+        let info = |reference: &str| {
+            handle_info(
+                &shape,
+                &parse_verse(reference).unwrap(),
+                None,
+                Some(&record),
+            )
+        };
+
+        let verse = info("Genesis 2:3").unwrap();
+        assert!(verse.contains("verse **34** of **56**"));
+        assert!(verse.contains("Parasha: **Bereshit** ~ aliyah 2"));
+        assert!(info("Genesis 1").unwrap().contains("aliyah 1"));
+        assert!(info("Genesis 3").is_err());
+        assert!(info("Genesis 2:26").is_err());
+        assert!(info("Genesis 1:1-3").is_err());
+    }
+}
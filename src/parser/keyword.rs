@@ -1,4 +1,12 @@
-use serde::{Deserialize, Serialize};
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+use super::bible_verse::{parse_verse, BibleRange, ReturnedBibleVerse};
+use super::hebrew::{is_hebrew, strip_nikkud};
+use super::search::{download_passage, format_passage, Language};
+use crate::common::download_json::post_download;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -9,6 +17,28 @@ pub struct Root {
     #[serde(rename = "_shards")]
     pub shards: Shards,
     pub hits: Hits,
+    #[serde(default)]
+    pub aggregations: Aggregations,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Aggregations {
+    pub path: Aggregation,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Aggregation {
+    pub buckets: Vec<Bucket>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Bucket {
+    pub key: String,
+    #[serde(rename = "doc_count")]
+    pub doc_count: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -23,9 +53,11 @@ pub struct Shards {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Hits {
+    #[serde(deserialize_with = "deserialize_total")]
     pub total: i64,
-    #[serde(rename = "max_score")]
-    pub max_score: f64,
+    // Null when hits are sorted by anything but relevance
+    #[serde(rename = "max_score", default)]
+    pub max_score: Option<f64>,
     pub hits: Vec<Hit>,
 }
 
@@ -36,13 +68,587 @@ pub struct Hit {
     pub index: String,
     #[serde(rename = "_id")]
     pub id: String,
-    #[serde(rename = "_score")]
-    pub score: f64,
+    #[serde(rename = "_score", default)]
+    pub score: Option<f64>,
+    #[serde(rename = "_source", default)]
+    pub source: Source,
     pub highlight: Highlight,
 }
 
+/// What a hit's `_id` is made of.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct HitRef {
+    pub reference: String,
+    pub version: String,
+    pub lang: String,
+}
+
+impl Hit {
+    /// Splits an `_id` like `Genesis 1:1 (The Contemporary Torah [en])` into its ref, version and
+    /// language.
+    pub fn hit_ref(&self) -> HitRef {
+        let id = self.id.trim();
+        let parsed = id.strip_suffix("])").and_then(|id| {
+            let (rest, lang) = id.rsplit_once(" [")?;
+            // Version titles can have parentheses of their own, refs can't
+            let (reference, version) = rest.split_once(" (")?;
+            Some(HitRef {
+                reference: reference.to_string(),
+                version: version.to_string(),
+                lang: lang.to_string(),
+            })
+        });
+        parsed.unwrap_or(HitRef {
+            reference: id.to_string(),
+            ..HitRef::default()
+        })
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Source {
+    #[serde(rename = "ref")]
+    pub reference: String,
+    pub he_ref: String,
+    pub version: String,
+    pub lang: String,
+    pub path: String,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct Highlight {
+    #[serde(default)]
     pub exact: Vec<String>,
+    #[serde(default)]
+    pub naive_lemmatizer: Vec<String>,
+}
+
+impl Highlight {
+    /// Whichever fragments came back, which depends on the field that was searched.
+    pub fn fragments(&self) -> impl Iterator<Item = &String> {
+        self.exact.iter().chain(self.naive_lemmatizer.iter())
+    }
+}
+
+/// How closely hits have to match the query.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Mode {
+    /// The exact words, in order
+    Exact,
+    /// Any form of the words, in order
+    Phrase,
+    /// Any form of the words, near each other
+    Fuzzy,
+}
+
+/// What order hits come back in.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Sort {
+    /// Best matches first
+    Relevance,
+    /// In the order books are listed in the table of contents
+    Canonical,
+    /// Oldest first, by when the book was composed
+    Chronological,
+}
+
+/// Newer versions of Elasticsearch send `{"value": 2340, "relation": "eq"}` instead of a number.
+fn deserialize_total<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    let total = Value::deserialize(deserializer)?;
+    Ok(total
+        .as_i64()
+        .or(total["value"].as_i64())
+        .unwrap_or_default())
+}
+
+/// What gets sent to the search-wrapper.
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct Query {
+    pub query: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    // `exact` only matches words as they are written, `naive_lemmatizer` matches other forms
+    pub field: String,
+    // How many words can come between the words of the query
+    pub slop: i32,
+    pub size: i32,
+    // The search-wrapper calls the offset `start` rather than Elasticsearch's `from`
+    pub start: i64,
+    // Paths like `Talmud/Bavli`, any of which a hit has to be filed under
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<String>,
+    // Which field every filter applies to, always `path` for us
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub filter_fields: Vec<String>,
+    pub source_proj: bool,
+    // Fields to count hits by, which only `path` is useful for
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aggs: Vec<String>,
+    // `score` sorts by relevance, `sort` by `sort_fields`
+    pub sort_method: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sort_fields: Vec<String>,
+    pub sort_reverse: bool,
+}
+
+impl Query {
+    pub fn new(query: &str, size: i32) -> Query {
+        Query {
+            query: query.to_string(),
+            kind: "text".to_string(),
+            field: "exact".to_string(),
+            slop: 0,
+            size,
+            start: 0,
+            filters: vec![],
+            filter_fields: vec![],
+            source_proj: true,
+            aggs: vec![],
+            sort_method: "score".to_string(),
+            sort_fields: vec![],
+            sort_reverse: false,
+        }
+    }
+
+    /// Hebrew is searched without nikkud, which the index doesn't have.
+    pub fn with_mode(query: &str, size: i32, mode: Mode, slop: Option<i32>) -> Query {
+        let query = if is_hebrew(query) {
+            strip_nikkud(query)
+        } else {
+            query.to_string()
+        };
+        let mut query = Query::new(query.trim(), size);
+        match mode {
+            Mode::Exact => (),
+            Mode::Phrase => query.field = "naive_lemmatizer".to_string(),
+            Mode::Fuzzy => {
+                query.field = "naive_lemmatizer".to_string();
+                query.slop = 10;
+            }
+        }
+        if let Some(slop) = slop {
+            query.slop = slop;
+        }
+        query
+    }
+
+    /// `order` is where the book is in the table of contents, `comp_date` is when it was written.
+    pub fn sort(&mut self, sort: Sort) {
+        let fields: &[&str] = match sort {
+            Sort::Relevance => &[],
+            Sort::Canonical => &["order"],
+            Sort::Chronological => &["comp_date", "order"],
+        };
+        self.sort_method = if fields.is_empty() { "score" } else { "sort" }.to_string();
+        self.sort_fields = fields.iter().map(|field| field.to_string()).collect();
+    }
+
+    pub fn filter(&mut self, path: String) {
+        self.filters.push(path);
+        self.filter_fields.push("path".to_string());
+    }
+}
+
+pub fn search_wrapper(query: &Query, parameters: Vec<(&str, &str)>) -> Result<Root, anyhow::Error> {
+    post_download(
+        "https://www.sefaria.org/api/search-wrapper",
+        serde_json::to_string(query)?,
+        parameters,
+    )
+}
+
+/// `2340` to `2,340`.
+pub fn thousands(number: i64) -> String {
+    let digits = number.abs().to_string();
+    let mut formatted = String::new();
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx != 0 && (digits.len() - idx).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    if number < 0 {
+        formatted.insert(0, '-');
+    }
+    formatted
+}
+
+/// Which of the total hits a page shows. `hidden` hits were on the page but have been taken out
+/// since, by `--exclude` or for being another version of a hit already shown.
+fn hits_header(results: &Root, from: i64, hidden: usize, show_score: bool) -> String {
+    let page_length = (results.hits.hits.len() + hidden) as i64;
+    if page_length == 0 {
+        return format!(
+            "**No hits past {} of {}**",
+            thousands(from),
+            thousands(results.hits.total)
+        );
+    }
+    let mut header = format!(
+        "**Showing {}–{} of {} hits**",
+        thousands(from + 1),
+        thousands(from + page_length),
+        thousands(results.hits.total),
+    );
+    if hidden > 0 {
+        header.push_str(&format!(" ({} hidden)", hidden));
+    }
+    if let (true, Some(max_score)) = (show_score, results.hits.max_score) {
+        header.push_str(&format!(" ~ best score {:.2}", max_score));
+    }
+    header
+}
+
+/// A hit's heading, with its score if asked for and there is one.
+fn hit_heading(heading: &str, hit: &Hit, show_score: bool) -> String {
+    match (show_score, hit.score) {
+        (true, Some(score)) => format!("# {} ~ {:.2}", heading, score),
+        _ => format!("# {}", heading),
+    }
+}
+
+/// Takes out hits whose ref is in `seen`, which would be the same passage in another version, and
+/// adds the rest to it. Returns how many were taken out.
+pub fn dedup_versions(hits: &mut Vec<Hit>, seen: &mut HashSet<String>) -> usize {
+    let length = hits.len();
+    hits.retain(|hit| seen.insert(hit.hit_ref().reference));
+    length - hits.len()
+}
+
+/// Formats one page of results, starting with which of the total hits it shows.
+pub fn format_hits(results: &Root, from: i64, hidden: usize, show_score: bool) -> String {
+    let mut formatted_string = vec![hits_header(results, from, hidden, show_score)];
+    for line in &results.hits.hits {
+        formatted_string.push("---".to_string());
+        formatted_string.push(hit_heading(&line.id, line, show_score));
+        for fragment in line.highlight.fragments() {
+            formatted_string.push(format!("> {}", html2md::parse_html(fragment)).to_string());
+        }
+    }
+    formatted_string.join("\n")
+}
+
+/// The words Sefaria highlighted in a hit, without nikkud so they can be found in any text.
+fn matched_words(hit: &Hit) -> Vec<String> {
+    let mut words = vec![];
+    for fragment in hit.highlight.fragments() {
+        for (idx, part) in fragment.split("<b>").enumerate() {
+            if idx == 0 {
+                continue;
+            }
+            if let Some((matched, _)) = part.split_once("</b>") {
+                words.extend(matched.split_whitespace().map(normalize_word));
+            }
+        }
+    }
+    words
+}
+
+pub fn normalize_word(word: &str) -> String {
+    strip_nikkud(word)
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Bolds every word in `text` that is one of `words`, ignoring nikkud and punctuation.
+pub fn emphasize(text: &str, words: &[String]) -> String {
+    text.split(' ')
+        .map(|token| {
+            let normalized = normalize_word(token);
+            if !normalized.is_empty() && words.contains(&normalized) {
+                format!("**{}**", token)
+            } else {
+                token.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `Genesis 1:5` with two verses of context is `Genesis 1:3-7`. Anything that isn't a single verse
+/// is left alone.
+fn with_context(reference: &str, context: usize) -> String {
+    match parse_verse(reference) {
+        Ok(ReturnedBibleVerse {
+            book,
+            section: Some(section),
+            verse: Some(BibleRange::Number(verse)),
+        }) if context > 0 => format!(
+            "{} {}:{}-{}",
+            book,
+            section,
+            verse.saturating_sub(context).max(1),
+            verse + context
+        ),
+        _ => reference.to_string(),
+    }
+}
+
+/// Fetches the whole segment a hit was found in, in the version it was found in, with the
+/// matched words in bold.
+fn expand_hit(
+    hit: &Hit,
+    context: usize,
+    lines: bool,
+    show_score: bool,
+    parameters: Vec<(&str, &str)>,
+) -> Result<String, anyhow::Error> {
+    let hit_ref = hit.hit_ref();
+    let reference = with_context(&hit_ref.reference, context);
+    let version_parameter = if hit_ref.lang == "he" { "vhe" } else { "ven" };
+    let mut parameters = parameters;
+    parameters.push((version_parameter, &hit_ref.version));
+
+    // Context can run past the end of a chapter, which Sefaria might not accept
+    let (reference, parsed_json) = match download_passage(&reference, parameters.clone()) {
+        Ok(parsed_json) => (reference, parsed_json),
+        Err(_) => (
+            hit_ref.reference.clone(),
+            download_passage(&hit_ref.reference, parameters)?,
+        ),
+    };
+    let language = if hit_ref.lang == "he" {
+        Language::Hebrew
+    } else {
+        Language::English
+    };
+    let body = format_passage(
+        &parsed_json,
+        parse_verse(&reference).unwrap_or(ReturnedBibleVerse {
+            book: reference.clone(),
+            section: None,
+            verse: None,
+        }),
+        lines,
+        language,
+        &[],
+    )?;
+
+    Ok(format!(
+        "{}\n{}",
+        hit_heading(
+            &format!(
+                "{} ~ {}",
+                parsed_json["ref"].as_str().unwrap_or(&reference),
+                hit_ref.version
+            ),
+            hit,
+            show_score
+        ),
+        emphasize(&body, &matched_words(hit))
+    ))
+}
+
+/// Like [`format_hits`], but shows the full text of every hit with `context` verses around it,
+/// with line numbers if `lines` is set.
+pub fn format_expanded_hits(
+    results: &Root,
+    from: i64,
+    hidden: usize,
+    context: usize,
+    lines: bool,
+    show_score: bool,
+    parameters: Vec<(&str, &str)>,
+) -> String {
+    let mut formatted_string = vec![hits_header(results, from, hidden, show_score)];
+    for hit in &results.hits.hits {
+        formatted_string.push("---".to_string());
+        match expand_hit(hit, context, lines, show_score, parameters.clone()) {
+            Ok(expanded) => formatted_string.push(expanded),
+            // Still show what we have from the search
+            Err(nar) => {
+                formatted_string.push(hit_heading(&hit.id, hit, show_score));
+                formatted_string.push(format!("*Could not expand: {}*", nar));
+                for fragment in hit.highlight.fragments() {
+                    formatted_string.push(format!("> {}", html2md::parse_html(fragment)));
+                }
+            }
+        }
+    }
+    formatted_string.join("\n")
+}
+
+/// Adds up bucket counts by everything in the path up to `depth` components, most hits first. The
+/// book at the end of a path is never counted as a category of its own.
+fn count_by(buckets: &[Bucket], depth: usize) -> Vec<(String, i64)> {
+    let mut counts: Vec<(String, i64)> = vec![];
+    for bucket in buckets {
+        let parts: Vec<_> = bucket.key.split('/').collect();
+        let key = parts[..depth.min(parts.len() - 1).max(1)].join("/");
+        match counts.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, count)) => *count += bucket.doc_count,
+            None => counts.push((key, bucket.doc_count)),
+        }
+    }
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts
+}
+
+fn facet_table(title: &str, counts: &[(String, i64)], chart: bool) -> String {
+    let max = counts.first().map_or(1, |(_, count)| (*count).max(1));
+    let mut table = if chart {
+        vec![
+            "|:-|-:|:-|".to_string(),
+            format!("|**{}**|**Hits**||", title),
+            "|:-|-:|:-|".to_string(),
+        ]
+    } else {
+        vec![
+            "|:-|-:|".to_string(),
+            format!("|**{}**|**Hits**|", title),
+            "|:-|-:|".to_string(),
+        ]
+    };
+    for (name, count) in counts {
+        table.push(if chart {
+            // 30 cells wide at most, but never hide something that has hits
+            let width = ((*count * 30 + max - 1) / max) as usize;
+            format!("|{}|{}|{}|", name, thousands(*count), "█".repeat(width))
+        } else {
+            format!("|{}|{}|", name, thousands(*count))
+        });
+    }
+    table.push("|-".to_string());
+    table.join("\n")
+}
+
+/// Summarizes where hits are by category down to `depth` and by book, optionally with bars.
+pub fn format_facets(results: &Root, depth: usize, chart: bool) -> String {
+    let buckets = &results.aggregations.path.buckets;
+    if buckets.is_empty() {
+        return "**No hits**".to_string();
+    }
+
+    let categories = count_by(buckets, depth);
+    // The last part of the path is always the book
+    let mut books: Vec<(String, i64)> = buckets
+        .iter()
+        .map(|bucket| {
+            (
+                bucket
+                    .key
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+                bucket.doc_count,
+            )
+        })
+        .collect();
+    books.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    [
+        format!("**{} hits**", thousands(results.hits.total)),
+        facet_table("Category", &categories, chart),
+        facet_table("Book", &books, chart),
+    ]
+    .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thousands_separators() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(2340), "2,340");
+        assert_eq!(thousands(1234567), "1,234,567");
+    }
+
+    #[test]
+    fn category_counts() {
+        let buckets = vec![
+            Bucket {
+                key: "Tanakh/Torah/Genesis".to_string(),
+                doc_count: 3,
+            },
+            Bucket {
+                key: "Talmud/Bavli/Seder Moed/Shabbat".to_string(),
+                doc_count: 5,
+            },
+            Bucket {
+                key: "Tanakh/Writings/Psalms".to_string(),
+                doc_count: 4,
+            },
+        ];
+        assert_eq!(
+            count_by(&buckets, 1),
+            vec![("Tanakh".to_string(), 7), ("Talmud".to_string(), 5)]
+        );
+        assert_eq!(
+            count_by(&buckets, 2),
+            vec![
+                ("Talmud/Bavli".to_string(), 5),
+                ("Tanakh/Writings".to_string(), 4),
+                ("Tanakh/Torah".to_string(), 3)
+            ]
+        );
+        assert_eq!(
+            count_by(&buckets[..1], 5),
+            vec![("Tanakh/Torah".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn hit_ids() {
+        let hit = Hit {
+            id: "Genesis 1:1 (The Contemporary Torah, Jewish Publication Society, 2006 [en])"
+                .to_string(),
+            ..Hit::default()
+        };
+        assert_eq!(
+            hit.hit_ref(),
+            HitRef {
+                reference: "Genesis 1:1".to_string(),
+                version: "The Contemporary Torah, Jewish Publication Society, 2006".to_string(),
+                lang: "en".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn dedups_versions() {
+        let hit = |id: &str| Hit {
+            id: id.to_string(),
+            ..Hit::default()
+        };
+        let mut seen = HashSet::new();
+        let mut hits = vec![
+            hit("Rashi on Genesis 1:1:1 (On Your Way [en])"),
+            hit("Rashi on Genesis 1:1:1 (Pentateuch with Rashi's commentary [en])"),
+            hit("Rashi on Genesis 1:2:1 (On Your Way [en])"),
+        ];
+        assert_eq!(dedup_versions(&mut hits, &mut seen), 1);
+        assert_eq!(hits.len(), 2);
+        // Later pages skip refs shown on earlier ones
+        let mut hits = vec![hit("Rashi on Genesis 1:2:1 (Sefaria Edition [he])")];
+        assert_eq!(dedup_versions(&mut hits, &mut seen), 1);
+    }
+
+    #[test]
+    fn context_around_verse() {
+        assert_eq!(with_context("Genesis 1:5", 2), "Genesis 1:3-7");
+        assert_eq!(with_context("Genesis 1:1", 2), "Genesis 1:1-3");
+        assert_eq!(with_context("Genesis 1:1", 0), "Genesis 1:1");
+    }
+
+    #[test]
+    fn emphasizes_with_nikkud() {
+        assert_eq!(
+            emphasize("> בְּרֵאשִׁ֖ית בָּרָ֣א אֱלֹהִ֑ים", &["ברא".to_string()]),
+            "> בְּרֵאשִׁ֖ית **בָּרָ֣א** אֱלֹהִ֑ים"
+        );
+    }
+
+    #[test]
+    fn object_total() {
+        let hits: Hits = serde_json::from_str(
+            r#"{"total": {"value": 2340, "relation": "eq"}, "max_score": 1.0, "hits": []}"#,
+        )
+        .unwrap();
+        assert_eq!(hits.total, 2340);
+    }
 }
@@ -0,0 +1,190 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+
+use super::search::download_passage;
+use super::shape::{shape_download, Shape};
+use super::toc::{resolve_path, Toc};
+
+/// A whole book saved for offline use, one entry per segment (verse, line or comment).
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedBook {
+    pub title: String,
+    pub he_title: String,
+    // Where the book is filed in the table of contents, like `Tanakh/Torah/Genesis`
+    pub path: String,
+    pub version: String,
+    pub he_version: String,
+    pub segments: Vec<CachedSegment>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedSegment {
+    #[serde(rename = "ref")]
+    pub reference: String,
+    pub en: String,
+    pub he: String,
+}
+
+/// Every string in a section's text along with where it is, since commentaries nest comments
+/// under verses and the usual texts don't.
+fn flatten(text: &Value, position: Vec<usize>, segments: &mut Vec<(Vec<usize>, String)>) {
+    match text {
+        Value::String(segment) if !segment.is_empty() => segments.push((position, segment.clone())),
+        Value::Array(parts) => {
+            for (idx, part) in parts.iter().enumerate() {
+                let mut position = position.clone();
+                position.push(idx + 1);
+                flatten(part, position, segments);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Lines up the English and Hebrew of a section by position, so that `Genesis 1` with `[2, 3]`
+/// becomes `Genesis 1:2:3`.
+fn section_segments(reference: &str, parsed_json: &Value) -> Vec<CachedSegment> {
+    let mut by_position: BTreeMap<Vec<usize>, CachedSegment> = BTreeMap::new();
+    for (field, hebrew) in [("text", false), ("he", true)] {
+        let mut segments = vec![];
+        flatten(&parsed_json[field], vec![], &mut segments);
+        for (position, segment) in segments {
+            let cached = by_position
+                .entry(position.clone())
+                .or_insert(CachedSegment {
+                    reference: std::iter::once(reference.to_string())
+                        .chain(position.iter().map(usize::to_string))
+                        .collect::<Vec<_>>()
+                        .join(":"),
+                    ..CachedSegment::default()
+                });
+            if hebrew {
+                cached.he = segment;
+            } else {
+                cached.en = segment;
+            }
+        }
+    }
+    by_position.into_values().collect()
+}
+
+/// Downloads every section of a book, which can take a while for anything long.
+pub fn download_book(
+    book: &str,
+    toc: &Toc,
+    parameters: Vec<(&str, &str)>,
+) -> Result<CachedBook, anyhow::Error> {
+    let shape: Shape = shape_download(
+        format!(
+            "https://www.sefaria.org/api/shape/{}",
+            urlencoding::encode(book)
+        )
+        .as_str(),
+        [("", "")].to_vec(),
+    )
+    .map_err(|err| anyhow!("Could not get shape of book: {}: {}", book, err))?;
+    let contents = shape
+        .first()
+        .ok_or(anyhow!("Sefaria does not know of a book called '{}'", book))?;
+
+    let mut cached = CachedBook {
+        title: contents.title.clone(),
+        he_title: contents.he_title.clone(),
+        path: resolve_path(toc, &contents.title).unwrap_or(contents.title.clone()),
+        ..CachedBook::default()
+    };
+    for (idx, length) in contents.chapters.iter().enumerate() {
+        // Skip amudim that do not exist, like 1a and 1b
+        if *length == 0 {
+            continue;
+        }
+        let reference = format!("{} {}", contents.title, contents.section(idx + 1));
+        let parsed_json = download_passage(&reference, parameters.clone())?;
+        if cached.version.is_empty() {
+            cached.version = parsed_json["versionTitle"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            cached.he_version = parsed_json["heVersionTitle"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+        }
+        cached
+            .segments
+            .extend(section_segments(&reference, &parsed_json));
+    }
+    Ok(cached)
+}
+
+/// Where cached books are kept, one JSON file each.
+fn book_file(title: &str) -> String {
+    format!("texts/{}.json", title.replace('/', "_"))
+}
+
+pub fn save_book(xdg_dirs: &xdg::BaseDirectories, book: &CachedBook) -> Result<(), anyhow::Error> {
+    let path = xdg_dirs.place_data_file(book_file(&book.title))?;
+    fs::write(path, serde_json::to_string(book)?)?;
+    Ok(())
+}
+
+/// The titles of every cached book, without reading them.
+pub fn cached_titles(xdg_dirs: &xdg::BaseDirectories) -> Vec<String> {
+    xdg_dirs
+        .list_data_files("texts")
+        .iter()
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .filter_map(|path| path.file_stem()?.to_str().map(str::to_string))
+        .collect()
+}
+
+/// Every book that has been cached, in no particular order.
+pub fn cached_books(xdg_dirs: &xdg::BaseDirectories) -> Result<Vec<CachedBook>, anyhow::Error> {
+    let mut books = vec![];
+    for path in xdg_dirs.list_data_files("texts") {
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            books.push(
+                serde_json::from_str(&fs::read_to_string(&path)?)
+                    .map_err(|err| anyhow!("Could not read {}: {}", path.display(), err))?,
+            );
+        }
+    }
+    Ok(books)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_of_commentary() {
+        let parsed_json = serde_json::json!({
+            "text": [["First", "Second"], [], ["Third"]],
+            "he": [["ראשון"], [], []],
+        });
+        let segments = section_segments("Rashi on Genesis 1", &parsed_json);
+        assert_eq!(
+            segments
+                .iter()
+                .map(|segment| segment.reference.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "Rashi on Genesis 1:1:1",
+                "Rashi on Genesis 1:1:2",
+                "Rashi on Genesis 1:3:1"
+            ]
+        );
+        assert_eq!(segments[0].he, "ראשון");
+        assert_eq!(segments[1].he, "");
+    }
+}
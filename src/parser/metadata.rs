@@ -0,0 +1,276 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::common::download_json::download;
+
+/// What Sefaria knows about a book, from its index record.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IndexRecord {
+    pub title: String,
+    pub he_title: String,
+    pub title_variants: Vec<String>,
+    pub he_title_variants: Vec<String>,
+    pub categories: Vec<String>,
+    // Either slugs like `rashi` or objects with `en` and `he` names
+    pub authors: Vec<Value>,
+    // A year, a list of years or nothing at all
+    pub comp_date: Value,
+    pub comp_place: Value,
+    pub era: String,
+    pub en_desc: String,
+    pub en_short_desc: String,
+    pub schema: Schema,
+    // Other ways of dividing the book, like `Parasha` for the Torah
+    #[serde(rename = "alt_structs")]
+    pub alt_structs: BTreeMap<String, AltStruct>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AltStruct {
+    pub nodes: Vec<AltNode>,
+}
+
+/// One part of an alternate structure, like a parasha along with its aliyot in `refs`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AltNode {
+    pub shared_title: String,
+    pub titles: Vec<AltTitle>,
+    pub whole_ref: String,
+    pub refs: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AltTitle {
+    pub text: String,
+    pub lang: String,
+    pub primary: bool,
+}
+
+impl AltNode {
+    /// The English name of the part, like `Noach`.
+    pub fn name(&self) -> &str {
+        if !self.shared_title.is_empty() {
+            return &self.shared_title;
+        }
+        self.titles
+            .iter()
+            .find(|title| title.lang == "en" && title.primary)
+            .or(self.titles.iter().find(|title| title.lang == "en"))
+            .map_or(self.whole_ref.as_str(), |title| title.text.as_str())
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Schema {
+    pub section_names: Vec<String>,
+    // Only complex books, like commentaries with an introduction, have nodes
+    pub nodes: Vec<SchemaNode>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SchemaNode {
+    pub key: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Version {
+    pub version_title: String,
+    pub language: String,
+}
+
+fn download_value(url: &str) -> Result<Value, anyhow::Error> {
+    let parsed_json = download(url, vec![])?;
+    match parsed_json.get("error") {
+        Some(error) => Err(anyhow!("{}", error.as_str().unwrap_or_default())),
+        None => Ok(parsed_json),
+    }
+}
+
+pub fn download_index_record(title: &str) -> Result<IndexRecord, anyhow::Error> {
+    Ok(serde_json::from_value(download_value(&format!(
+        "https://www.sefaria.org/api/v2/raw/index/{}",
+        urlencoding::encode(title)
+    ))?)?)
+}
+
+/// An index record, downloaded once and then kept in the data dir like the shapes of a category.
+pub fn cached_index_record(
+    xdg_dirs: &xdg::BaseDirectories,
+    title: &str,
+) -> Result<IndexRecord, anyhow::Error> {
+    let file = format!("index_records/{}.json", title);
+    if let Some(found) = xdg_dirs.find_data_file(&file) {
+        return Ok(serde_json::from_str(&fs::read_to_string(found)?)?);
+    }
+    let record = download_index_record(title)?;
+    fs::write(
+        xdg_dirs.place_data_file(&file)?,
+        serde_json::to_string(&record)?,
+    )?;
+    Ok(record)
+}
+
+pub fn download_versions(title: &str) -> Result<Vec<Version>, anyhow::Error> {
+    Ok(serde_json::from_value(download_value(&format!(
+        "https://www.sefaria.org/api/texts/versions/{}",
+        urlencoding::encode(title)
+    ))?)?)
+}
+
+/// `ibn-ezra` to `Ibn Ezra`.
+fn from_slug(slug: &str) -> String {
+    slug.split(['-', '_', ' '])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn year(year: &Value) -> Option<String> {
+    let year = year.as_i64().or_else(|| year.as_str()?.parse().ok())?;
+    Some(if year < 0 {
+        format!("{} BCE", -year)
+    } else {
+        year.to_string()
+    })
+}
+
+/// `[1075, 1105]` to `1075–1105`, as composition dates are often only known to a range.
+fn comp_date(date: &Value) -> Option<String> {
+    match date {
+        Value::Array(years) => {
+            let years: Vec<String> = years.iter().filter_map(year).collect();
+            match (years.first(), years.last()) {
+                (Some(first), Some(last)) if first == last => Some(first.clone()),
+                (Some(first), Some(last)) => Some(format!("{}–{}", first, last)),
+                _ => None,
+            }
+        }
+        date => year(date),
+    }
+}
+
+fn era(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "T" => "Tannaim",
+        "A" => "Amoraim",
+        "GN" => "Geonim",
+        "RI" => "Rishonim",
+        "AH" => "Acharonim",
+        "CO" => "Contemporary",
+        _ => return None,
+    })
+}
+
+fn authors(authors: &[Value]) -> Vec<String> {
+    authors
+        .iter()
+        .filter_map(|author| match author {
+            Value::String(slug) => Some(from_slug(slug)),
+            author => author["en"].as_str().map(str::to_string),
+        })
+        .collect()
+}
+
+/// How a book is divided, like `Chapter → Verse`, or its parts if it has several.
+fn structure(schema: &Schema) -> String {
+    if schema.nodes.is_empty() {
+        schema.section_names.join(" → ")
+    } else {
+        schema
+            .nodes
+            .iter()
+            .map(|node| node.key.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Everything known about a book as a table, followed by its description and versions.
+pub fn format_card(record: &IndexRecord, versions: &[Version]) -> String {
+    let mut rows: Vec<(&str, String)> = vec![
+        ("Hebrew title", record.he_title.clone()),
+        ("Category", record.categories.join(" → ")),
+        ("Authors", authors(&record.authors).join(", ")),
+        ("Composed", comp_date(&record.comp_date).unwrap_or_default()),
+        (
+            "Place",
+            record
+                .comp_place
+                .as_str()
+                .map(from_slug)
+                .unwrap_or_default(),
+        ),
+        ("Era", era(&record.era).unwrap_or_default().to_string()),
+        ("Structure", structure(&record.schema)),
+    ];
+    let titles: Vec<&str> = record
+        .title_variants
+        .iter()
+        .chain(&record.he_title_variants)
+        .filter(|title| **title != record.title && **title != record.he_title)
+        .map(String::as_str)
+        .collect();
+    rows.insert(1, ("Also known as", titles.join(", ")));
+    rows.retain(|(_, value)| !value.is_empty());
+
+    let mut card = vec![format!("# {}", record.title), "|:-|:-|".to_string()];
+    for (name, value) in rows {
+        card.push(format!("|**{}**|{}|", name, value.replace('|', "/")));
+    }
+    card.push("|-".to_string());
+
+    let description = if record.en_desc.is_empty() {
+        &record.en_short_desc
+    } else {
+        &record.en_desc
+    };
+    if !description.is_empty() {
+        card.push(format!("\n{}", description));
+    }
+    if !versions.is_empty() {
+        card.push("\n## Versions".to_string());
+        for version in versions {
+            card.push(format!(
+                "* [{}] {}",
+                version.language, version.version_title
+            ));
+        }
+    }
+    card.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_index_records() {
+        let record: IndexRecord = serde_json::from_str(
+            r#"{"title": "Rashi on Genesis", "authors": ["rashi", {"en": "Ibn Ezra"}],
+                "compDate": [1075, 1105], "compPlace": "troyes", "era": "RI",
+                "schema": {"nodeType": "JaggedArrayNode", "sectionNames": ["Chapter", "Verse", "Comment"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(authors(&record.authors), ["Rashi", "Ibn Ezra"]);
+        assert_eq!(comp_date(&record.comp_date).unwrap(), "1075–1105");
+        assert_eq!(comp_date(&serde_json::json!(-500)).unwrap(), "500 BCE");
+        assert_eq!(structure(&record.schema), "Chapter → Verse → Comment");
+    }
+}
@@ -0,0 +1,219 @@
+use clap::ValueEnum;
+use pest::Parser;
+use std::collections::HashMap;
+
+use super::bible_verse::{parse_verse, BibleVerse, Rule};
+use super::search::{download_passage, Language};
+use super::text::{convert_to_text, strip_tags};
+use super::toc::{books, Toc};
+
+// What `whitespace_with_commas` in the grammar allows between the words of a book
+static SEPARATORS: [char; 4] = [' ', ',', '\t', '_'];
+
+/// What `scan` prints.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ScanOutput {
+    /// Every reference, once each
+    List,
+    /// The document with every reference linked to Sefaria
+    Links,
+    /// The document with the text of every reference quoted under it
+    Inline,
+}
+
+/// A reference found in a document, by where it is and what it refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub start: usize,
+    pub end: usize,
+    pub reference: String,
+}
+
+/// The book of a citation is every word up to the section, like `As seen in Genesis`, so this
+/// finds the longest run of words at the end of it that is a title in the table of contents.
+fn find_title(book: &str, titles: &HashMap<String, String>) -> Option<(usize, String)> {
+    let mut starts = vec![0];
+    starts.extend(
+        book.char_indices()
+            .filter(|(_, c)| SEPARATORS.contains(c))
+            .map(|(idx, _)| idx + 1),
+    );
+    starts.into_iter().find_map(|start| {
+        let words = book[start..]
+            .split(SEPARATORS)
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        titles
+            .get(&words.to_lowercase())
+            .map(|title| (start, title.clone()))
+    })
+}
+
+/// Every reference to a book in the table of contents, in the order they show up.
+pub fn find_citations(text: &str, toc: &Toc) -> Vec<Citation> {
+    let titles: HashMap<String, String> = books(toc)
+        .iter()
+        .map(|book| (book.title.to_lowercase(), book.title.clone()))
+        .collect();
+    let Ok(mut parsed) = BibleVerse::parse(Rule::citations, text) else {
+        return vec![];
+    };
+
+    let mut citations = vec![];
+    for citation in parsed.next().unwrap().into_inner() {
+        if citation.as_rule() != Rule::citation {
+            continue;
+        }
+        let span = citation.as_span();
+        let Some(book) = citation
+            .clone()
+            .into_inner()
+            .find(|pair| pair.as_rule() == Rule::book)
+        else {
+            continue;
+        };
+        let Some((offset, title)) = find_title(book.as_str(), &titles) else {
+            continue;
+        };
+        // Skip the `.`, `_` or space between the book and the section
+        let identifier = &text[book.as_span().end() + 1..span.end()];
+        let reference = format!("{} {}", title, identifier);
+        if parse_verse(&reference).is_ok() {
+            citations.push(Citation {
+                start: book.as_span().start() + offset,
+                end: span.end(),
+                reference,
+            });
+        }
+    }
+    citations
+}
+
+/// `Genesis 1:3-5` to `https://www.sefaria.org/Genesis.1.3-5`.
+pub fn sefaria_url(reference: &str) -> String {
+    let (book, identifier) = reference.rsplit_once(' ').unwrap_or((reference, ""));
+    let path = format!(
+        "{}.{}",
+        book.replace(' ', "_"),
+        identifier.replace([':', ','], ".")
+    );
+    format!(
+        "https://www.sefaria.org/{}",
+        urlencoding::encode(path.trim_end_matches('.'))
+    )
+}
+
+/// Every reference once, in the order they are first cited.
+pub fn format_list(citations: &[Citation]) -> String {
+    let mut references: Vec<&str> = vec![];
+    for citation in citations {
+        if !references.contains(&citation.reference.as_str()) {
+            references.push(&citation.reference);
+        }
+    }
+    references.join("\n")
+}
+
+/// The document with every citation turned into a Markdown link.
+pub fn link_citations(text: &str, citations: &[Citation]) -> String {
+    let mut linked = String::with_capacity(text.len());
+    let mut last = 0;
+    for citation in citations {
+        linked.push_str(&text[last..citation.start]);
+        linked.push_str(&format!(
+            "[{}]({})",
+            &text[citation.start..citation.end],
+            sefaria_url(&citation.reference)
+        ));
+        last = citation.end;
+    }
+    linked.push_str(&text[last..]);
+    linked
+}
+
+/// The document with the text of everything cited in a paragraph quoted after that paragraph.
+pub fn inline_citations(
+    text: &str,
+    citations: &[Citation],
+    parameters: Vec<(&str, &str)>,
+    language: Language,
+) -> String {
+    let field = match language {
+        Language::English => "text",
+        _ => "he",
+    };
+    let mut inlined = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut pending: Vec<&Citation> = vec![];
+    let quote = |inlined: &mut String, pending: &mut Vec<&Citation>| {
+        for citation in pending.drain(..) {
+            let quoted = match download_passage(&citation.reference, parameters.clone()) {
+                Ok(parsed_json) => convert_to_text(&parsed_json[field])
+                    .map(|lines| strip_tags(&lines.join(" ")))
+                    .unwrap_or_default(),
+                Err(nar) => format!("*Could not get text: {}*", nar),
+            };
+            inlined.push_str(&format!("\n> **{}**\n> {}\n", citation.reference, quoted));
+        }
+    };
+
+    for (idx, _) in text.match_indices("\n\n") {
+        pending.extend(
+            citations
+                .iter()
+                .filter(|c| c.start >= last && c.start < idx),
+        );
+        inlined.push_str(&text[last..idx]);
+        if !pending.is_empty() {
+            inlined.push('\n');
+            quote(&mut inlined, &mut pending);
+        }
+        last = idx;
+    }
+    pending.extend(citations.iter().filter(|c| c.start >= last));
+    inlined.push_str(text[last..].trim_end());
+    if !pending.is_empty() {
+        inlined.push('\n');
+        quote(&mut inlined, &mut pending);
+    }
+    inlined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::fixtures;
+
+    #[test]
+    fn finds_citations() {
+        let text = "As seen in Genesis 1:3-5, and song of songs 2, but not Chapter 4 \
+                    or Genesis abc. See I Samuel 3:4 and Berakhot 2a.";
+        let references: Vec<String> = find_citations(text, &fixtures::toc())
+            .into_iter()
+            .map(|citation| citation.reference)
+            .collect();
+        assert_eq!(
+            references,
+            [
+                "Genesis 1:3-5",
+                "Song of Songs 2",
+                "I Samuel 3:4",
+                "Berakhot 2a"
+            ]
+        );
+    }
+
+    #[test]
+    fn links_citations() {
+        let text = "See Genesis 1:3.";
+        assert_eq!(
+            link_citations(text, &find_citations(text, &fixtures::toc())),
+            "See [Genesis 1:3](https://www.sefaria.org/Genesis.1.3)."
+        );
+        assert_eq!(
+            sefaria_url("Song of Songs 2"),
+            "https://www.sefaria.org/Song_of_Songs.2"
+        );
+    }
+}
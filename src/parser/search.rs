@@ -0,0 +1,363 @@
+use anyhow::anyhow;
+use serde_json::Value;
+
+use super::{
+    bible_verse::{parse_section_range, parse_verse, ReturnedBibleVerse, Section},
+    shape::{shape_download, Shape, ShapeContents},
+    tetragrammaton::check_for_tetra,
+    text::convert_to_sections,
+};
+use crate::common::download_json::download;
+use crate::common::marks::{book_notes, Mark};
+use crate::logging::log::{suggested_path, Log};
+
+// A whole book is downloaded a chapter or amud at a time, so longer ones need `--chapters`
+static MAX_SECTIONS: usize = 50;
+
+/// Which text of a passage gets shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    English,
+    Hebrew,
+    // Hebrew with the English beneath every verse
+    Bilingual,
+}
+
+impl Language {
+    /// The short name the shell's `lang` takes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Hebrew => "he",
+            Language::Bilingual => "bi",
+        }
+    }
+}
+
+/// A formatted passage along with where it sits in its book.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Passage {
+    pub reference: String,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+    pub language: Language,
+    // The version Sefaria picked, shown in history
+    pub version: String,
+    pub text: String,
+}
+
+pub fn download_passage(
+    reference: &str,
+    parameters: Vec<(&str, &str)>,
+) -> Result<Value, anyhow::Error> {
+    let parsed_json = download(
+        format!(
+            "https://www.sefaria.org/api/texts/{}",
+            urlencoding::encode(reference)
+        )
+        .as_str(),
+        parameters,
+    )?;
+
+    match parsed_json.get("error") {
+        Some(error) => Err(anyhow!("{}", error.as_str().unwrap_or_default())),
+        None => Ok(parsed_json),
+    }
+}
+
+/// The title of the version a passage is shown in, which is the Hebrew one unless only English
+/// was asked for and there is a translation.
+pub fn version_title(parsed_json: &Value, language: Language) -> String {
+    match parsed_json["versionTitle"].as_str() {
+        Some(title) if language == Language::English && !title.is_empty() => title.to_string(),
+        _ => parsed_json["heVersionTitle"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Heading for a single chapter or amud, such as `## Chapter 3` or `## Daf 2a`.
+fn section_heading(parsed_json: &Value, section: &Section) -> String {
+    let name = parsed_json["sectionNames"][0]
+        .as_str()
+        .unwrap_or(match section {
+            Section::Chapter(_) => "Chapter",
+            Section::Daf(_) => "Daf",
+        });
+    format!("## {} {}\n", name, section)
+}
+
+/// Formats the verses of a downloaded passage as quotes, adding a heading for every chapter or
+/// amud once the passage spans more than one of them. Every note in `notes` goes under the first
+/// verse shown that it's on.
+pub fn format_passage(
+    parsed_json: &Value,
+    parsed_verse: ReturnedBibleVerse,
+    lines: bool,
+    language: Language,
+    notes: &[Mark],
+) -> Result<String, anyhow::Error> {
+    let mut formatted_string: Vec<String> = vec![];
+    let english = convert_to_sections(&parsed_json["text"]).unwrap_or_default();
+    let hebrew = convert_to_sections(&parsed_json["he"])?;
+
+    // Plenty of texts were never translated, so those are always shown in Hebrew
+    let language = if english.concat().is_empty() {
+        Language::Hebrew
+    } else {
+        language
+    };
+    let sections = match language {
+        Language::English => &english,
+        Language::Hebrew | Language::Bilingual => &hebrew,
+    };
+    let text = sections.concat();
+
+    // Sefaria hands back the normalized ref of what it actually returned, which is what
+    // we should label lines by, so we fall back on what the user typed in.
+    let returned_verse = parsed_json["ref"]
+        .as_str()
+        .and_then(|normalized| parse_verse(normalized).ok())
+        .unwrap_or(parsed_verse);
+    let (first_section, first_verse) = returned_verse
+        .starting_point()?
+        .unwrap_or((Section::Chapter(1), 1));
+    let notes = book_notes(notes, &returned_verse.book);
+    let mut shown_notes = vec![false; notes.len()];
+
+    if check_for_tetra(&text) {
+        let path = suggested_path();
+        let log = Log::new(&path).unwrap();
+        log.log(text.clone());
+    }
+
+    let multiple_sections = sections.len() > 1;
+    for (section_idx, section) in sections.iter().enumerate() {
+        let start = if section_idx == 0 { first_verse } else { 1 };
+        if multiple_sections {
+            formatted_string.push(section_heading(
+                parsed_json,
+                &first_section.offset(section_idx),
+            ));
+        }
+        for (idx, line) in section.iter().enumerate() {
+            let line = html2md::parse_html(line);
+            formatted_string.push(if lines {
+                format!("> *{}* {}", idx + start, line)
+            } else {
+                format!("> {}", line)
+            });
+
+            if language == Language::Bilingual {
+                if let Some(translation) = english
+                    .get(section_idx)
+                    .and_then(|section| section.get(idx))
+                {
+                    formatted_string.push(format!("\n>\n> {}", html2md::parse_html(translation)));
+                }
+            }
+
+            let point = (first_section.offset(section_idx).index(), idx + start);
+            for (note_idx, (first, last, note)) in notes.iter().enumerate() {
+                if !shown_notes[note_idx] && (*first..=*last).contains(&point) {
+                    shown_notes[note_idx] = true;
+                    for line in note.lines() {
+                        formatted_string.push(format!("\n>\n> *✎ {}*", line));
+                    }
+                }
+            }
+
+            if idx != section.len() - 1 {
+                formatted_string.push("\n>\n".to_string());
+            } else {
+                formatted_string.push("\n".to_string());
+            }
+        }
+    }
+
+    Ok(formatted_string.join(""))
+}
+
+/// Downloads and formats a single reference.
+pub fn search_passage(
+    reference: &str,
+    parameters: Vec<(&str, &str)>,
+    lines: bool,
+    language: Language,
+    notes: &[Mark],
+) -> Result<Passage, anyhow::Error> {
+    let parsed_verse = parse_verse(reference)?;
+    let parsed_json = download_passage(reference, parameters)?;
+    let returned_reference = parsed_json["ref"].as_str().unwrap_or(reference);
+
+    Ok(Passage {
+        reference: returned_reference.to_string(),
+        next: parsed_json["next"].as_str().map(str::to_string),
+        prev: parsed_json["prev"].as_str().map(str::to_string),
+        language,
+        version: version_title(&parsed_json, language),
+        text: format!(
+            "# {} ~ {}\n---\n{}",
+            returned_reference,
+            parsed_json["type"].as_str().unwrap_or_default(),
+            format_passage(&parsed_json, parsed_verse, lines, language, notes)?
+        ),
+    })
+}
+
+/// The first and last index of the sections of `contents` that `chapters` asks for, or of the
+/// whole book. Chapters have to be asked for by chapter and dafim by daf, and no more than
+/// `MAX_SECTIONS` of them at once.
+fn section_bounds(
+    contents: &ShapeContents,
+    chapters: Option<&str>,
+) -> Result<(usize, usize), anyhow::Error> {
+    let (first, last) = match chapters {
+        Some(chapters) => {
+            let (first, last) = parse_section_range(chapters)?;
+            match (first, contents.is_daf_based()) {
+                (Section::Chapter(_), true) => {
+                    return Err(anyhow!(
+                        "{} is divided into dafim, so '--chapters {}' should be like `2a-5b`",
+                        contents.title,
+                        chapters
+                    ))
+                }
+                (Section::Daf(_), false) => {
+                    return Err(anyhow!(
+                        "{} is divided into chapters, so '--chapters {}' should be like `3-5`",
+                        contents.title,
+                        chapters
+                    ))
+                }
+                _ => {}
+            }
+            (first.index(), last.index())
+        }
+        None => (1, contents.chapters.len()),
+    };
+    if first > last || last > contents.chapters.len() {
+        return Err(anyhow!(
+            "{} only goes up to {}, so '{}' is out of range",
+            contents.title,
+            contents.section(contents.chapters.len()),
+            chapters.unwrap_or_default()
+        ));
+    }
+    let count = contents.chapters[first - 1..last]
+        .iter()
+        .filter(|length| **length > 0)
+        .count();
+    if count > MAX_SECTIONS {
+        return Err(anyhow!(
+            "That is {} sections of {}, more than the {} shown at once, so pick some with \
+             `--chapters`",
+            count,
+            contents.title,
+            MAX_SECTIONS
+        ));
+    }
+    Ok((first, last))
+}
+
+/// Walks every chapter (or amud) of a book using its shape, optionally limited to `chapters`,
+/// which is either a single section or a range like `3-5` or `2a-5b`.
+pub fn search_book(
+    book: &str,
+    chapters: Option<&str>,
+    parameters: Vec<(&str, &str)>,
+    lines: bool,
+    language: Language,
+    notes: &[Mark],
+) -> Result<Passage, anyhow::Error> {
+    let shape: Shape = shape_download(
+        format!(
+            "https://www.sefaria.org/api/shape/{}",
+            urlencoding::encode(book)
+        )
+        .as_str(),
+        [("", "")].to_vec(),
+    )
+    .map_err(|err| anyhow!("Could not get shape of book: {}: {}", book, err))?;
+    let contents = shape
+        .first()
+        .ok_or(anyhow!("Sefaria does not know of a book called '{}'", book))?;
+
+    let (first, last) = section_bounds(contents, chapters)?;
+
+    let mut formatted_string: Vec<String> = vec![];
+    let mut passage = Passage {
+        reference: String::default(),
+        next: None,
+        prev: None,
+        language,
+        version: String::default(),
+        text: String::default(),
+    };
+    for index in first..=last {
+        // Skip amudim that do not exist, like 1a and 1b
+        if contents.chapters[index - 1] == 0 {
+            continue;
+        }
+        let section = contents.section(index);
+        let reference = format!("{} {}", contents.title, section);
+        let parsed_json = download_passage(&reference, parameters.clone())?;
+
+        if formatted_string.is_empty() {
+            passage.prev = parsed_json["prev"].as_str().map(str::to_string);
+            formatted_string.push(format!(
+                "# {} ~ {}\n---\n",
+                match chapters {
+                    Some(chapters) => format!("{} {}", contents.title, chapters),
+                    None => contents.title.clone(),
+                },
+                parsed_json["type"].as_str().unwrap_or_default()
+            ));
+        }
+        formatted_string.push(section_heading(&parsed_json, &section));
+        formatted_string.push(format_passage(
+            &parsed_json,
+            parse_verse(&reference)?,
+            lines,
+            language,
+            notes,
+        )?);
+        passage.next = parsed_json["next"].as_str().map(str::to_string);
+        passage.version = version_title(&parsed_json, language);
+        passage.reference = reference;
+    }
+
+    passage.text = formatted_string.join("");
+    Ok(passage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_match_the_book() {
+        let book = |title: &str, chapters: Vec<i64>| ShapeContents {
+            title: title.to_string(),
+            chapters,
+            ..ShapeContents::default()
+        };
+        let genesis = book("Genesis", vec![31; 50]);
+        let mut berakhot = book("Berakhot", vec![10; 127]);
+        berakhot.chapters[..2].fill(0);
+        let psalms = book("Psalms", vec![6; 150]);
+
+        assert_eq!(section_bounds(&genesis, None).unwrap(), (1, 50));
+        assert_eq!(section_bounds(&genesis, Some("3-5")).unwrap(), (3, 5));
+        assert!(section_bounds(&genesis, Some("2a-5b")).is_err());
+        assert_eq!(section_bounds(&berakhot, Some("2a-3a")).unwrap(), (3, 5));
+        assert!(section_bounds(&berakhot, Some("3-5")).is_err());
+        assert!(section_bounds(&berakhot, None).is_err());
+        assert!(section_bounds(&psalms, None).is_err());
+        assert_eq!(
+            section_bounds(&psalms, Some("101-150")).unwrap(),
+            (101, 150)
+        );
+    }
+}
@@ -1,6 +1,7 @@
-use reqwest;
+use super::bible_verse::Section;
+use super::daf::Daf;
+use crate::common::download_json::{download, try_get_text};
 use serde::{Deserialize, Serialize};
-use serde_json::Result;
 
 pub type Shape = Vec<ShapeContents>;
 
@@ -16,15 +17,50 @@ pub struct ShapeContents {
     pub he_book: String,
 }
 
-pub fn shape_download(url: &str, parameters: Vec<(&str, &str)>) -> Result<Shape> {
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .get(url)
-        .query(&parameters)
-        .send()
-        .unwrap()
-        .text()
-        .unwrap();
+impl ShapeContents {
+    /// Talmud tractates are addressed by daf/amud, and since every tractate starts on `2a`, Sefaria
+    /// leaves the sections for `1a` and `1b` empty, which nothing chapter based does.
+    pub fn is_daf_based(&self) -> bool {
+        self.chapters.len() > 2 && self.chapters[0] == 0 && self.chapters[1] == 0
+    }
 
-    serde_json::from_str(&response)
+    /// The section at a 1-based position in `chapters`.
+    pub fn section(&self, index: usize) -> Section {
+        if self.is_daf_based() {
+            Section::Daf(Daf::from_index(index).unwrap())
+        } else {
+            Section::Chapter(index)
+        }
+    }
+}
+
+pub fn shape_download(url: &str, parameters: Vec<(&str, &str)>) -> Result<Shape, anyhow::Error> {
+    Ok(serde_json::from_value(download(url, parameters)?)?)
+}
+
+/// The shapes of every book in a category, downloaded once and then kept in the data dir so that
+/// anything worked out from them can be done offline.
+pub fn category_shapes(
+    xdg_dirs: &xdg::BaseDirectories,
+    path: &str,
+) -> Result<Shape, anyhow::Error> {
+    let file = format!("shapes/{}.json", path.replace('/', "_"));
+    if let Some(found) = xdg_dirs.find_data_file(&file) {
+        return Ok(serde_json::from_str(&std::fs::read_to_string(found)?)?);
+    }
+    let shapes: Shape = serde_json::from_str(
+        &try_get_text(
+            &format!(
+                "https://www.sefaria.org/api/shape/{}",
+                urlencoding::encode(path)
+            ),
+            [("", "")].to_vec(),
+        )
+        .map_err(|_| anyhow::anyhow!("Could not download the shapes of {}", path))?,
+    )?;
+    std::fs::write(
+        xdg_dirs.place_data_file(&file)?,
+        serde_json::to_string(&shapes)?,
+    )?;
+    Ok(shapes)
 }
@@ -21,3 +21,38 @@ pub fn convert_to_text(text: &Value) -> Result<Vec<&str>, anyhow::Error> {
         Err(anyhow!("Could convert 'text' to string or array: {}", text))
     }
 }
+
+/// Like [`convert_to_text`], but keeps every section (chapter or amud) in its own vector so that
+/// lines can be labeled with the section they came from.
+pub fn convert_to_sections(text: &Value) -> Result<Vec<Vec<&str>>, anyhow::Error> {
+    if text.is_array()
+        && text
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|piece| piece.is_array())
+    {
+        let mut sections: Vec<Vec<&str>> = vec![];
+        for piece in text.as_array().unwrap() {
+            sections.push(convert_to_text(piece)?);
+        }
+        Ok(sections)
+    } else {
+        Ok(vec![convert_to_text(text)?])
+    }
+}
+
+/// Takes out the HTML Sefaria leaves in texts.
+pub fn strip_tags(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => stripped.push(c),
+            _ => (),
+        }
+    }
+    stripped.replace("&nbsp;", " ").replace("&amp;", "&")
+}
@@ -0,0 +1,157 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::setup::download::toc_path;
+
+/// The Sefaria table of contents, as saved by `setup_toc`.
+pub type Toc = Vec<TocNode>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TocNode {
+    Category(TocCategory),
+    Book(TocBook),
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TocCategory {
+    pub category: String,
+    #[serde(default)]
+    pub he_category: String,
+    pub contents: Vec<TocNode>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TocBook {
+    pub title: String,
+    #[serde(default)]
+    pub he_title: String,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+pub fn load_toc(path: &Path) -> Result<Toc, anyhow::Error> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Loads the table of contents from where `setup_toc` put it.
+pub fn default_toc() -> Result<Toc, anyhow::Error> {
+    load_toc(&toc_path())
+}
+
+/// Every book in the table of contents, in the order Sefaria lists them.
+pub fn books(toc: &[TocNode]) -> Vec<&TocBook> {
+    let mut books = vec![];
+    for node in toc {
+        match node {
+            TocNode::Category(category) => books.extend(self::books(&category.contents)),
+            TocNode::Book(book) => books.push(book),
+        }
+    }
+    books
+}
+
+fn collect_paths(toc: &[TocNode], parent: &str, paths: &mut Vec<(String, String)>) {
+    for node in toc {
+        let name = match node {
+            TocNode::Category(category) => &category.category,
+            TocNode::Book(book) => &book.title,
+        };
+        let path = if parent.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", parent, name)
+        };
+        paths.push((name.clone(), path.clone()));
+        if let TocNode::Category(category) = node {
+            collect_paths(&category.contents, &path, paths);
+        }
+    }
+}
+
+/// Finds the path Sefaria files a category or book under, so `Talmud` stays `Talmud`, `Mishneh
+/// Torah` becomes `Halakhah/Mishneh Torah` and `Genesis` becomes `Tanakh/Torah/Genesis`. Paths
+/// like `Talmud/Bavli` are checked as they are.
+pub fn resolve_path(toc: &[TocNode], name: &str) -> Result<String, anyhow::Error> {
+    let mut paths = vec![];
+    collect_paths(toc, "", &mut paths);
+
+    let name = name.trim().trim_matches('/');
+    paths
+        .into_iter()
+        .find(|(title, path)| {
+            if name.contains('/') {
+                path.eq_ignore_ascii_case(name)
+            } else {
+                title.eq_ignore_ascii_case(name)
+            }
+        })
+        .map(|(_, path)| path)
+        .ok_or(anyhow!(
+            "'{}' is not a category or book in the table of contents",
+            name
+        ))
+}
+
+/// The category `name` resolves to along with its path, or `None` if it's a book or nothing at all.
+pub fn find_category<'a>(toc: &'a [TocNode], name: &str) -> Option<(String, &'a TocCategory)> {
+    let path = resolve_path(toc, name).ok()?;
+    let mut nodes = toc;
+    let mut found = None;
+    for part in path.split('/') {
+        let category = nodes.iter().find_map(|node| match node {
+            TocNode::Category(category) if category.category == part => Some(category),
+            _ => None,
+        })?;
+        nodes = &category.contents;
+        found = Some(category);
+    }
+    found.map(|category| (path, category))
+}
+
+/// Whether `path` is `prefix` or anything filed under it.
+pub fn in_path(path: &str, prefix: &str) -> bool {
+    path == prefix || path.starts_with(&format!("{}/", prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::fixtures;
+
+    #[test]
+    fn resolves_categories_and_books() {
+        let toc = fixtures::toc();
+        assert_eq!(resolve_path(&toc, "Tanakh").unwrap(), "Tanakh");
+        assert_eq!(
+            resolve_path(&toc, "mishneh torah").unwrap(),
+            "Halakhah/Mishneh Torah"
+        );
+        assert_eq!(
+            resolve_path(&toc, "Genesis").unwrap(),
+            "Tanakh/Torah/Genesis"
+        );
+        assert_eq!(resolve_path(&toc, "Tanakh/Torah").unwrap(), "Tanakh/Torah");
+        assert!(resolve_path(&toc, "Torah/Genesis").is_err());
+    }
+
+    #[test]
+    fn finds_categories() {
+        let toc = fixtures::toc();
+        let (path, category) = find_category(&toc, "torah").unwrap();
+        assert_eq!(path, "Tanakh/Torah");
+        assert_eq!(books(&category.contents)[0].title, "Genesis");
+        assert!(find_category(&toc, "Genesis").is_none());
+    }
+
+    #[test]
+    fn path_prefixes() {
+        assert!(in_path("Tanakh/Torah/Genesis", "Tanakh/Torah"));
+        assert!(in_path("Tanakh/Torah", "Tanakh/Torah"));
+        assert!(!in_path("Tanakh/Torahs", "Tanakh/Torah"));
+    }
+}
@@ -0,0 +1,335 @@
+use anyhow::anyhow;
+use chrono::Local;
+use serde_json::Value;
+use std::io::{stdout, Write};
+use std::path::Path;
+use termimad::crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use termimad::{Area, MadSkin, MadView};
+
+use crate::common::history::{append_visit, Visit};
+use crate::common::marks::{load_marks, Mark};
+use crate::common::state::LastViewed;
+use crate::ferror;
+use crate::parser::bible_verse::parse_verse;
+use crate::parser::search::{download_passage, format_passage, version_title, Language};
+
+static HELP: &str = "n/p chapter  h language  l numbers  g go to  / find  [/] matches  q quit";
+
+/// Everything but the last row, which is kept for the status bar.
+fn view_area() -> Area {
+    let mut area = Area::full_screen();
+    area.height = area.height.saturating_sub(1);
+    area.pad_for_max_width(120);
+    area
+}
+
+struct Reader<'a> {
+    xdg_dirs: &'a xdg::BaseDirectories,
+    parameters: Vec<(&'a str, &'a str)>,
+    // What was asked for, which always parses, unlike what Sefaria normalizes it to
+    requested: String,
+    reference: String,
+    parsed_json: Value,
+    lines: bool,
+    language: Language,
+    // Loaded once, so that notes show under their verses on every passage
+    marks: Vec<Mark>,
+    search: Option<String>,
+    // Scroll positions of every match of `search`
+    matches: Vec<usize>,
+    current_match: usize,
+    message: Option<String>,
+}
+
+impl Reader<'_> {
+    fn load(&mut self, reference: &str) -> Result<(), anyhow::Error> {
+        parse_verse(reference)?;
+        let parsed_json = download_passage(reference, self.parameters.clone())?;
+        self.reference = parsed_json["ref"].as_str().unwrap_or(reference).to_string();
+        self.requested = reference.to_string();
+        self.parsed_json = parsed_json;
+        self.search = None;
+
+        // Every passage read goes in the history, not just the one the reader is closed on
+        let visit = Visit {
+            reference: self.reference.clone(),
+            time: Local::now(),
+            language: self.language.code().to_string(),
+            version: version_title(&self.parsed_json, self.language),
+        };
+        if let Err(nar) = append_visit(self.xdg_dirs, &visit) {
+            self.message = Some(format!("Could not add passage to history: {}", nar));
+        }
+        Ok(())
+    }
+
+    fn adjacent(&self, key: &str) -> Option<String> {
+        self.parsed_json[key].as_str().map(str::to_string)
+    }
+
+    fn markdown(&self) -> Result<String, anyhow::Error> {
+        let mut markdown = format!(
+            "# {} ~ {}\n---\n{}",
+            self.reference,
+            self.parsed_json["type"].as_str().unwrap_or_default(),
+            format_passage(
+                &self.parsed_json,
+                parse_verse(&self.requested)?,
+                self.lines,
+                self.language,
+                &self.marks,
+            )?
+        );
+        if let Some(search) = &self.search {
+            markdown = highlight(&markdown, search);
+        }
+        Ok(markdown)
+    }
+
+    /// Finds the line every match starts on once rendered, by rendering everything before it.
+    fn find_matches(&mut self, markdown: &str, skin: &MadSkin) {
+        self.matches.clear();
+        self.current_match = 0;
+        let Some(search) = &self.search else {
+            return;
+        };
+        let area = view_area();
+        let lowered = markdown.to_ascii_lowercase();
+        let needle = format!("`{}`", search.to_ascii_lowercase());
+        for (position, _) in lowered.match_indices(&needle) {
+            let line_start = markdown[..position].rfind('\n').unwrap_or(0);
+            let scroll = skin.area_text(&markdown[..line_start], &area).lines.len();
+            if self.matches.last() != Some(&scroll) {
+                self.matches.push(scroll);
+            }
+        }
+    }
+
+    fn status(&self) -> String {
+        let language = match self.language {
+            Language::English => "en",
+            Language::Hebrew => "he",
+            Language::Bilingual => "he/en",
+        };
+        let matches = match &self.search {
+            Some(search) => format!(
+                " ~ '{}' {}/{}",
+                search,
+                (self.current_match + 1).min(self.matches.len()),
+                self.matches.len()
+            ),
+            None => String::default(),
+        };
+        match &self.message {
+            Some(message) => format!("{} [{}]{} ~ {}", self.reference, language, matches, message),
+            None => format!("{} [{}]{} ~ {}", self.reference, language, matches, HELP),
+        }
+    }
+
+    fn run<W: Write>(&mut self, w: &mut W, skin: &MadSkin) -> Result<(), anyhow::Error> {
+        let mut markdown = self.markdown()?;
+        let mut view = MadView::from(markdown.clone(), view_area(), skin.clone());
+        loop {
+            view.write_on(w)?;
+            let (width, height) = terminal::size()?;
+            let mut status = self.status();
+            status.truncate(
+                status
+                    .char_indices()
+                    .nth(width as usize)
+                    .map_or(status.len(), |(idx, _)| idx),
+            );
+            queue!(
+                w,
+                MoveTo(0, height.saturating_sub(1)),
+                Clear(ClearType::CurrentLine),
+                SetAttribute(Attribute::Reverse),
+                Print(status),
+                SetAttribute(Attribute::Reset)
+            )?;
+            w.flush()?;
+
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Resize(..) => {
+                    queue!(w, Clear(ClearType::All))?;
+                    view.resize(&view_area());
+                    self.find_matches(&markdown, skin);
+                    continue;
+                }
+                _ => continue,
+            };
+            self.message = None;
+
+            let mut rebuild = true;
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('n') | KeyCode::Char('p') => {
+                    let key = if key.code == KeyCode::Char('n') {
+                        "next"
+                    } else {
+                        "prev"
+                    };
+                    match self.adjacent(key) {
+                        Some(reference) => {
+                            if let Err(nar) = self.load(&reference) {
+                                self.message = Some(nar.to_string());
+                            }
+                        }
+                        None => self.message = Some(format!("There is no {} passage", key)),
+                    }
+                }
+                KeyCode::Char('h') => {
+                    self.language = match self.language {
+                        Language::English => Language::Hebrew,
+                        Language::Hebrew => Language::Bilingual,
+                        Language::Bilingual => Language::English,
+                    };
+                }
+                KeyCode::Char('l') => self.lines = !self.lines,
+                KeyCode::Char('g') => {
+                    if let Some(reference) = prompt(w, "Go to: ")? {
+                        if let Err(nar) = self.load(&reference) {
+                            self.message = Some(nar.to_string());
+                        }
+                    }
+                }
+                KeyCode::Char('/') => {
+                    self.search = prompt(w, "Find: ")?.filter(|search| !search.is_empty());
+                }
+                KeyCode::Char(']') | KeyCode::Char('[') => {
+                    rebuild = false;
+                    if !self.matches.is_empty() {
+                        let count = self.matches.len();
+                        self.current_match = if key.code == KeyCode::Char(']') {
+                            (self.current_match + 1) % count
+                        } else {
+                            (self.current_match + count - 1) % count
+                        };
+                        view.scroll = self.matches[self.current_match];
+                    }
+                }
+                _ => {
+                    rebuild = false;
+                    view.apply_key_event(key);
+                }
+            }
+
+            if rebuild {
+                markdown = self.markdown()?;
+                queue!(w, Clear(ClearType::All))?;
+                view = MadView::from(markdown.clone(), view_area(), skin.clone());
+                self.find_matches(&markdown, skin);
+                if let Some(scroll) = self.matches.first() {
+                    view.scroll = *scroll;
+                } else if self.search.is_some() {
+                    self.message = Some("No matches".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Wraps every occurrence of `search` in inline code so the skin makes it stand out.
+fn highlight(markdown: &str, search: &str) -> String {
+    let lowered = markdown.to_ascii_lowercase();
+    let needle = search.to_ascii_lowercase();
+    let mut highlighted = String::with_capacity(markdown.len());
+    let mut last = 0;
+    for (position, _) in lowered.match_indices(&needle) {
+        highlighted.push_str(&markdown[last..position]);
+        highlighted.push_str(&format!(
+            "`{}`",
+            &markdown[position..position + needle.len()]
+        ));
+        last = position + needle.len();
+    }
+    highlighted.push_str(&markdown[last..]);
+    highlighted
+}
+
+/// Reads a line of input in the status bar, `None` if it was cancelled with escape.
+fn prompt<W: Write>(w: &mut W, label: &str) -> Result<Option<String>, anyhow::Error> {
+    let (_, height) = terminal::size()?;
+    let mut input = String::new();
+    queue!(w, Show)?;
+    let result = loop {
+        queue!(
+            w,
+            MoveTo(0, height.saturating_sub(1)),
+            Clear(ClearType::CurrentLine),
+            Print(format!("{}{}", label, input))
+        )?;
+        w.flush()?;
+        if let Event::Key(KeyEvent { code, .. }) = event::read()? {
+            match code {
+                KeyCode::Enter => break Some(input.trim().to_string()),
+                KeyCode::Esc => break None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => (),
+            }
+        }
+    };
+    queue!(w, Hide)?;
+    Ok(result)
+}
+
+/// Opens a full screen, scrollable view of `reference`. Whatever was being read when the reader is
+/// closed becomes the last viewed passage, and every passage it loads is added to the history.
+pub fn read(
+    reference: &str,
+    skin: &MadSkin,
+    xdg_dirs: &xdg::BaseDirectories,
+    parameters: Vec<(&str, &str)>,
+    state_path: &Path,
+    lines: bool,
+    language: Language,
+) -> Result<(), anyhow::Error> {
+    let marks = load_marks(xdg_dirs).unwrap_or_else(|nar| {
+        ferror!("Could not load notes: {}", nar);
+        vec![]
+    });
+    let mut reader = Reader {
+        xdg_dirs,
+        parameters,
+        requested: String::default(),
+        reference: String::default(),
+        parsed_json: Value::Null,
+        lines,
+        language,
+        marks,
+        search: None,
+        matches: vec![],
+        current_match: 0,
+        message: None,
+    };
+    // Load before taking over the terminal so a bad reference is reported normally
+    reader.load(reference)?;
+
+    let mut w = stdout();
+    queue!(w, EnterAlternateScreen, Hide)?;
+    terminal::enable_raw_mode()?;
+    let result = reader.run(&mut w, skin);
+    terminal::disable_raw_mode()?;
+    queue!(w, Show, LeaveAlternateScreen)?;
+    w.flush()?;
+
+    LastViewed {
+        reference: reader.reference.clone(),
+        next: reader.adjacent("next"),
+        prev: reader.adjacent("prev"),
+    }
+    .save(state_path)
+    .map_err(|err| anyhow!("Could not remember last viewed passage: {}", err))?;
+    result
+}
@@ -4,10 +4,8 @@ use std::fs::File;
 use std::io;
 use std::path::{Path, PathBuf};
 
-pub fn setup_toc() {
-    let mut resp = reqwest::blocking::get("https://www.sefaria.org/api/index/")
-        .expect("Failed to download Table of Contents");
-    let path: PathBuf = [
+pub fn toc_path() -> PathBuf {
+    [
         env::var("HOME").unwrap().as_str(),
         ".local",
         "share",
@@ -15,9 +13,15 @@ pub fn setup_toc() {
         "toc.json",
     ]
     .iter()
-    .collect();
+    .collect()
+}
+
+pub fn setup_toc() {
+    let path = toc_path();
 
     if !Path::new(&path).exists() {
+        let mut resp = reqwest::blocking::get("https://www.sefaria.org/api/index/")
+            .expect("Failed to download Table of Contents");
         std::fs::create_dir_all(path.parent().unwrap()).unwrap();
 
         let mut out =
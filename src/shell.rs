@@ -0,0 +1,180 @@
+use anyhow::anyhow;
+use clap::{CommandFactory, Parser};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+
+use crate::commands::{run_command, Context};
+use crate::common::history::{load_history, recent_refs};
+use crate::ferror;
+use crate::parser::args::Args;
+use crate::parser::search::Language;
+use crate::parser::toc::{books, default_toc};
+
+static HELP: &str = "\
+Any rsbbi command can be run here, like `s Genesis 1`, `k two spines` or `i Exodus`.
+
+Shell only commands:
+  lang <en|he|bi>  Language used when `--hebrew` isn't given
+  help             Show this, or `help <command>` for a command
+  quit             Leave the shell";
+
+/// Completes command names, then refs from the history and book names from the table of contents.
+struct ShellHelper {
+    commands: Vec<String>,
+    // Most recently viewed first, so they come before any book
+    refs: Vec<String>,
+    books: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let (start, options): (usize, Vec<&String>) = match line.find(' ') {
+            None => (0, self.commands.iter().collect()),
+            Some(_) => {
+                // Refs and books come after the command and any flags
+                let mut start = 0;
+                for (idx, word) in line.split(' ').enumerate() {
+                    if idx == 0 || word.starts_with('-') {
+                        start = word.as_ptr() as usize - line.as_ptr() as usize + word.len() + 1;
+                    }
+                }
+                (
+                    start.min(line.len()),
+                    self.refs.iter().chain(&self.books).collect(),
+                )
+            }
+        };
+
+        let partial = line[start..].to_lowercase();
+        let candidates = options
+            .iter()
+            .filter(|option| option.to_lowercase().starts_with(&partial))
+            .map(|option| Pair {
+                display: option.to_string(),
+                replacement: option.to_string(),
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Handles commands that only make sense in the shell, returning `false` once it should exit.
+fn shell_command(words: &[String], context: &mut Context) -> Result<bool, anyhow::Error> {
+    match words.first().map(String::as_str) {
+        Some("quit") | Some("exit") => return Ok(false),
+        Some("help") if words.len() == 1 => println!("{}", HELP),
+        Some("lang") => {
+            context.language = match words.get(1).map(String::as_str) {
+                Some("en") => Language::English,
+                Some("he") => Language::Hebrew,
+                Some("bi") => Language::Bilingual,
+                _ => return Err(anyhow!("Expected one of 'en', 'he' or 'bi'")),
+            };
+        }
+        _ => {
+            let args = Args::try_parse_from(
+                std::iter::once("rsbbi".to_string()).chain(words.iter().cloned()),
+            );
+            match args {
+                Ok(args) => run_command(&args.cmd, context)?,
+                // Also covers `--help`, which clap hands back as an error
+                Err(err) => err.print()?,
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Runs commands one line at a time, reusing one context (and with it, one client and cache).
+pub fn run(context: &mut Context) -> Result<(), anyhow::Error> {
+    let commands = Args::command()
+        .get_subcommands()
+        .flat_map(|command| {
+            std::iter::once(command.get_name().to_string())
+                .chain(command.get_all_aliases().map(str::to_string))
+        })
+        .chain(["lang", "help", "quit"].map(str::to_string))
+        .collect();
+    let books = match default_toc() {
+        Ok(toc) => books(&toc).iter().map(|book| book.title.clone()).collect(),
+        Err(nar) => {
+            ferror!(
+                "Could not load table of contents, books won't complete: {}",
+                nar
+            );
+            vec![]
+        }
+    };
+
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper {
+        commands,
+        refs: recent_refs(&load_history(&context.xdg_dirs).unwrap_or_default()),
+        books,
+    }));
+    let history_path = context.xdg_dirs.place_state_file("shell_history")?;
+    // There is no history the first time around
+    let _ = editor.load_history(&history_path);
+
+    context.in_shell = true;
+    loop {
+        let prompt = match context.language {
+            Language::English => "rsbbi> ",
+            Language::Hebrew => "rsbbi [he]> ",
+            Language::Bilingual => "rsbbi [bi]> ",
+        };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line.as_str())?;
+
+        let Some(words) = shlex::split(&line) else {
+            ferror!("Unbalanced quotes in: {}", line);
+            continue;
+        };
+        match shell_command(&words, context) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(nar) => {
+                ferror!("{}", nar);
+            }
+        }
+        if let (Some(helper), Ok(history)) = (editor.helper_mut(), load_history(&context.xdg_dirs))
+        {
+            helper.refs = recent_refs(&history);
+        }
+    }
+    context.in_shell = false;
+
+    editor.save_history(&history_path)?;
+    Ok(())
+}